@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vixen_bis_asset::{check_mlod, P3dSettings};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = check_mlod(data, &P3dSettings::default());
+});
@@ -0,0 +1,273 @@
+use bevy::{prelude::*, reflect::TypeUuid, utils::HashMap};
+
+use crate::config::{ConfigCursor, ConfigValue};
+
+/// Animation source values, applied to all P3D scenes below the entity.
+#[derive(Component, Clone, Debug, Default)]
+pub struct AnimationSources(HashMap<String, f32>);
+
+impl AnimationSources {
+    pub fn get(&self, source: &str) -> f32 {
+        self.0
+            .get(&source.to_ascii_lowercase())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set(&mut self, source: &str, value: f32) {
+        self.0.insert(source.to_ascii_lowercase(), value);
+    }
+}
+
+/// Animations of a model, as defined by `class Animations` in model.cfg.
+#[derive(TypeUuid, Clone, Debug, Default)]
+#[uuid = "6f8b2c3e-5a0d-4b7e-9c41-2d3f8e1a7b65"]
+pub struct P3dAnimations(pub Vec<P3dAnimation>);
+
+#[derive(Clone, Debug)]
+pub struct P3dAnimation {
+    pub name: String,
+    pub source: String,
+    /// Animated bone, named selection of the model.
+    pub selection: String,
+    pub kind: P3dAnimationKind,
+    pub min_value: f32,
+    pub max_value: f32,
+    pub source_address: P3dSourceAddress,
+}
+
+#[derive(Clone, Debug)]
+pub enum P3dAnimationKind {
+    Rotation {
+        origin: Vec3,
+        axis: Vec3,
+        angle0: f32,
+        angle1: f32,
+    },
+    Translation {
+        /// Translation for an offset of one.
+        axis: Vec3,
+        offset0: f32,
+        offset1: f32,
+    },
+    Hide {
+        hide_value: f32,
+        unhide_value: Option<f32>,
+    },
+}
+
+/// Mapping of source values outside of the animation's range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum P3dSourceAddress {
+    #[default]
+    Clamp,
+    Mirror,
+    Loop,
+}
+
+impl P3dAnimation {
+    /// Reads an animation class, axes are resolved to points by the given function.
+    pub fn from_config(
+        class: &ConfigCursor,
+        points: impl Fn(&str) -> Option<Vec<Vec3>>,
+    ) -> Option<Self> {
+        let string = |name: &str| {
+            class
+                .value(name)
+                .and_then(ConfigValue::as_str)
+                .unwrap_or_default()
+        };
+        let number =
+            |name: &str, default: f32| class.value(name).and_then(config_number).unwrap_or(default);
+
+        // Axis is either given by a selection of two points or by begin and end selections
+        let (begin, end) = match points(string("axis")) {
+            Some(axis) if axis.len() >= 2 => (axis[0], axis[1]),
+            axis => {
+                let begin = points(string("begin"))
+                    .and_then(|points| points.first().copied())
+                    .or_else(|| axis.as_ref().and_then(|axis| axis.first().copied()))
+                    .unwrap_or_default();
+                let end = points(string("end"))
+                    .and_then(|points| points.first().copied())
+                    .unwrap_or(begin);
+                (begin, end)
+            }
+        };
+
+        let kind = match string("type").to_ascii_lowercase().as_str() {
+            type_ @ ("rotation" | "rotationx" | "rotationy" | "rotationz") => {
+                P3dAnimationKind::Rotation {
+                    origin: begin,
+                    axis: match type_ {
+                        "rotationx" => Vec3::X,
+                        "rotationy" => Vec3::Y,
+                        "rotationz" => Vec3::Z,
+                        _ => (end - begin).normalize_or_zero(),
+                    },
+                    angle0: number("angle0", 0.0),
+                    angle1: number("angle1", 0.0),
+                }
+            }
+            type_ @ ("translation" | "translationx" | "translationy" | "translationz") => {
+                P3dAnimationKind::Translation {
+                    axis: match type_ {
+                        "translationx" => Vec3::X,
+                        "translationy" => Vec3::Y,
+                        "translationz" => Vec3::Z,
+                        _ => end - begin,
+                    },
+                    offset0: number("offset0", 0.0),
+                    offset1: number("offset1", 0.0),
+                }
+            }
+            "hide" => P3dAnimationKind::Hide {
+                hide_value: number("hideValue", 0.0),
+                unhide_value: class.value("unHideValue").and_then(config_number),
+            },
+            _ => return None,
+        };
+
+        Some(Self {
+            name: class.name().to_string(),
+            source: string("source").to_string(),
+            selection: string("selection").to_string(),
+            kind,
+            min_value: number("minValue", 0.0),
+            max_value: number("maxValue", 1.0),
+            source_address: match string("sourceAddress").to_ascii_lowercase().as_str() {
+                "mirror" => P3dSourceAddress::Mirror,
+                "loop" => P3dSourceAddress::Loop,
+                _ => P3dSourceAddress::Clamp,
+            },
+        })
+    }
+
+    /// Maps a source value to the animation's phase in the range of 0 to 1.
+    pub fn phase(&self, value: f32) -> f32 {
+        let range = self.max_value - self.min_value;
+        if range == 0.0 {
+            return 0.0;
+        }
+
+        let phase = (value - self.min_value) / range;
+        match self.source_address {
+            P3dSourceAddress::Clamp => phase.clamp(0.0, 1.0),
+            P3dSourceAddress::Mirror => 1.0 - ((phase.rem_euclid(2.0)) - 1.0).abs(),
+            P3dSourceAddress::Loop => phase.rem_euclid(1.0),
+        }
+    }
+
+    /// Returns the model space transform for a source value, or none if hidden.
+    pub fn transform(&self, value: f32) -> Option<Mat4> {
+        let phase = self.phase(value);
+        match self.kind {
+            P3dAnimationKind::Rotation {
+                origin,
+                axis,
+                angle0,
+                angle1,
+            } => Some(
+                Mat4::from_translation(origin)
+                    * Mat4::from_axis_angle(axis, angle0 + (angle1 - angle0) * phase)
+                    * Mat4::from_translation(-origin),
+            ),
+            P3dAnimationKind::Translation {
+                axis,
+                offset0,
+                offset1,
+            } => Some(Mat4::from_translation(
+                axis * (offset0 + (offset1 - offset0) * phase),
+            )),
+            P3dAnimationKind::Hide {
+                hide_value,
+                unhide_value,
+            } => {
+                if phase >= hide_value && unhide_value.map_or(true, |value| phase < value) {
+                    None
+                } else {
+                    Some(Mat4::IDENTITY)
+                }
+            }
+        }
+    }
+}
+
+/// Numbers can be given as expressions, `rad` is the only supported one.
+fn config_number(value: &ConfigValue) -> Option<f32> {
+    value.as_f32().or_else(|| {
+        value
+            .as_str()?
+            .trim()
+            .strip_prefix("rad")?
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .map(f32::to_radians)
+    })
+}
+
+/// Drives model.cfg animations of spawned P3D scenes by their animation sources.
+pub(crate) fn animate_sources(
+    sources: Query<(Entity, &AnimationSources), Or<(Changed<AnimationSources>, Changed<Children>)>>,
+    children: Query<&Children>,
+    animators: Query<&Handle<P3dAnimations>>,
+    names: Query<&Name>,
+    mut transforms: Query<&mut Transform>,
+    animations: Res<Assets<P3dAnimations>>,
+) {
+    for (entity, source_values) in sources.iter() {
+        // Find all model roots
+        let mut stack = vec![entity];
+        while let Some(entity) = stack.pop() {
+            let Ok(entity_children) = children.get(entity) else {
+                continue;
+            };
+            let Some(model_animations) = animators
+                .get(entity)
+                .ok()
+                .and_then(|handle| animations.get(handle))
+            else {
+                stack.extend(entity_children.iter());
+                continue;
+            };
+
+            // Collect joints by name
+            let mut joints = HashMap::default();
+            let mut joint_stack: Vec<Entity> = entity_children.iter().copied().collect();
+            while let Some(joint) = joint_stack.pop() {
+                if let Ok(name) = names.get(joint) {
+                    joints.insert(name.as_str().to_ascii_lowercase(), joint);
+                }
+                if let Ok(joint_children) = children.get(joint) {
+                    joint_stack.extend(joint_children.iter());
+                }
+            }
+
+            // Compose animations per bone, in the order they are defined
+            let mut bone_transforms: HashMap<String, Option<Mat4>> = HashMap::default();
+            for animation in &model_animations.0 {
+                let bone_transform = bone_transforms
+                    .entry(animation.selection.to_ascii_lowercase())
+                    .or_insert(Some(Mat4::IDENTITY));
+                *bone_transform = bone_transform.and_then(|bone_transform| {
+                    Some(
+                        animation.transform(source_values.get(&animation.source))? * bone_transform,
+                    )
+                });
+            }
+            for (bone, bone_transform) in bone_transforms {
+                let Some(&joint) = joints.get(&bone) else {
+                    continue;
+                };
+                let Ok(mut transform) = transforms.get_mut(joint) else {
+                    continue;
+                };
+                *transform = match bone_transform {
+                    Some(bone_transform) => Transform::from_matrix(bone_transform),
+                    None => Transform::from_scale(Vec3::ZERO),
+                };
+            }
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::BufReader,
+    path::{Component, Path, PathBuf},
+    process::ExitCode,
+};
+
+use anyhow::{bail, Result};
+use serde_json::{json, Map, Value};
+use vixen_bis_asset::{
+    AssetConverter, ConfigClass, ConfigEntry, ConfigValue, MlodInfo, OdolInfo, P3dSettings,
+    P3dValidation, PaaInfo, Pbo,
+};
+
+const USAGE: &str = "\
+Usage: vixen-bis [--json] <file>
+       vixen-bis extract <pbo> <directory> [<path>...]
+       vixen-bis convert <directory> <input>...
+
+Dumps the structure of a P3D, PAA, config or PBO, or extracts files from a PBO, optionally only
+the ones starting with the given paths, or converts the files of PBOs and directories, including
+their PBOs, into glTF, KTX2 and material descriptions with a manifest.
+
+Binarized ODOL models are dumped up to their LOD resolutions, their LODs are not read.";
+
+fn main() -> ExitCode {
+    let arguments: Vec<_> = env::args().skip(1).collect();
+    let result = match arguments
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["extract", pbo, directory, paths @ ..] => {
+            extract(Path::new(pbo), Path::new(directory), paths)
+        }
+        ["convert", directory, inputs @ ..] if !inputs.is_empty() => {
+            convert(Path::new(directory), inputs)
+        }
+        ["--json", file] => dump(Path::new(file), true),
+        [file] if !file.starts_with('-') => dump(Path::new(file), false),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn dump(path: &Path, json: bool) -> Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if extension == "pbo" {
+        let pbo = Pbo::read_from(&mut BufReader::new(File::open(path)?))?;
+        return print(json, dump_pbo_json(&pbo), || dump_pbo_text(&pbo));
+    }
+
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(b"MLOD") {
+        let (mlod, diagnostics) = MlodInfo::read(&bytes, P3dValidation::Lenient)?;
+        for diagnostic in &diagnostics.0 {
+            eprintln!("warning: {diagnostic}");
+        }
+        print(json, dump_mlod_json(&mlod), || dump_mlod_text(&mlod))
+    } else if bytes.starts_with(b"ODOL") {
+        let odol = OdolInfo::read(&bytes)?;
+        print(json, dump_odol_json(&odol), || dump_odol_text(&odol))
+    } else if matches!(extension.as_str(), "paa" | "pac") {
+        let paa = PaaInfo::read(&bytes)?;
+        print(json, dump_paa_json(&paa), || dump_paa_text(&paa))
+    } else if bytes.starts_with(b"\0raP")
+        || matches!(
+            extension.as_str(),
+            "cpp" | "hpp" | "cfg" | "rvmat" | "bisurf" | "sqm" | "ext"
+        )
+    {
+        let config = ConfigClass::from_bytes(&bytes)?;
+        print(json, config_json(&config), || print!("{config}"))
+    } else {
+        bail!("unknown file type of {path:?}")
+    }
+}
+
+fn print(json: bool, value: Value, text: impl FnOnce()) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&value)?);
+    } else {
+        text();
+    }
+    Ok(())
+}
+
+fn dump_mlod_text(mlod: &MlodInfo) {
+    for (index, lod) in mlod.lods.iter().enumerate() {
+        println!("LOD {index}: {:?} ({})", lod.kind, lod.resolution);
+        println!(
+            "  {} points, {} normals, {} faces",
+            lod.points, lod.normals, lod.faces
+        );
+        for texture in &lod.textures {
+            println!("  texture {texture}");
+        }
+        for material in &lod.materials {
+            println!("  material {material}");
+        }
+        for selection in &lod.selections {
+            println!("  selection {selection}");
+        }
+        for (name, value) in &lod.properties {
+            println!("  property {name} = {value}");
+        }
+    }
+}
+
+fn dump_odol_text(odol: &OdolInfo) {
+    println!("ODOL version {}", odol.version);
+    if !odol.prefix.is_empty() {
+        println!("prefix {}", odol.prefix);
+    }
+    for (index, (resolution, kind)) in odol.lods.iter().enumerate() {
+        println!("LOD {index}: {kind:?} ({resolution})");
+    }
+}
+
+fn dump_odol_json(odol: &OdolInfo) -> Value {
+    json!({
+        "format": "ODOL",
+        "version": odol.version,
+        "prefix": odol.prefix,
+        "lods": odol.lods.iter().map(|(resolution, kind)| json!({
+            "resolution": resolution,
+            "kind": format!("{kind:?}"),
+        })).collect::<Vec<_>>(),
+    })
+}
+
+fn dump_mlod_json(mlod: &MlodInfo) -> Value {
+    json!({
+        "format": "MLOD",
+        "lods": mlod.lods.iter().map(|lod| json!({
+            "resolution": lod.resolution,
+            "kind": format!("{:?}", lod.kind),
+            "points": lod.points,
+            "normals": lod.normals,
+            "faces": lod.faces,
+            "textures": lod.textures,
+            "materials": lod.materials,
+            "selections": lod.selections,
+            "properties": string_map(&lod.properties),
+        })).collect::<Vec<_>>(),
+    })
+}
+
+fn dump_paa_text(paa: &PaaInfo) {
+    println!("{:?}, palette of {} colors", paa.format, paa.palette);
+    for (name, value) in &paa.tags {
+        println!("  tag {name} = {value}");
+    }
+    for (index, mipmap) in paa.mipmaps.iter().enumerate() {
+        println!(
+            "  mipmap {index}: {}x{}, {} bytes",
+            mipmap.width, mipmap.height, mipmap.size
+        );
+    }
+}
+
+fn dump_paa_json(paa: &PaaInfo) -> Value {
+    json!({
+        "format": format!("{:?}", paa.format),
+        "tags": string_map(&paa.tags),
+        "palette": paa.palette,
+        "mipmaps": paa.mipmaps.iter().map(|mipmap| json!({
+            "width": mipmap.width,
+            "height": mipmap.height,
+            "size": mipmap.size,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+fn dump_pbo_text(pbo: &Pbo) {
+    for (key, value) in &pbo.extensions {
+        println!("{key} = {value}");
+    }
+    for entry in &pbo.entries {
+        println!(
+            "{:>10} {:>10} {}{}",
+            entry.original_size.max(entry.data_size),
+            entry.timestamp,
+            entry.name,
+            if entry.compressed {
+                " (compressed)"
+            } else {
+                ""
+            }
+        );
+    }
+}
+
+fn dump_pbo_json(pbo: &Pbo) -> Value {
+    json!({
+        "extensions": string_map(&pbo.extensions),
+        "entries": pbo.entries.iter().map(|entry| json!({
+            "name": entry.name,
+            "compressed": entry.compressed,
+            "original_size": entry.original_size,
+            "data_size": entry.data_size,
+            "timestamp": entry.timestamp,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+fn string_map(pairs: &[(String, String)]) -> Map<String, Value> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+        .collect()
+}
+
+/// Converts classes to objects, with the parent as `extends`, forward declarations to null and
+/// array expansions to objects with a `+=` array.
+fn config_json(class: &ConfigClass) -> Value {
+    let mut object = Map::new();
+    if let Some(parent) = &class.parent {
+        object.insert("extends".to_string(), Value::String(parent.clone()));
+    }
+    for (name, entry) in &class.entries {
+        let value = match entry {
+            ConfigEntry::Class(class) => config_json(class),
+            ConfigEntry::Extern => Value::Null,
+            ConfigEntry::Delete => json!({ "delete": true }),
+            ConfigEntry::Value(value) => config_value_json(value),
+            ConfigEntry::Expansion(values) => {
+                json!({ "+=": values.iter().map(config_value_json).collect::<Vec<_>>() })
+            }
+        };
+        object.insert(name.clone(), value);
+    }
+    Value::Object(object)
+}
+
+fn config_value_json(value: &ConfigValue) -> Value {
+    match value {
+        ConfigValue::String(value) => json!(value),
+        ConfigValue::Float(value) => json!(value),
+        ConfigValue::Int(value) => json!(value),
+        ConfigValue::Array(values) => values.iter().map(config_value_json).collect(),
+    }
+}
+
+fn extract(path: &Path, directory: &Path, paths: &[&str]) -> Result<()> {
+    let mut input = BufReader::new(File::open(path)?);
+    let pbo = Pbo::read_from(&mut input)?;
+    for entry in &pbo.entries {
+        let name = entry.name.replace('\\', "/");
+        if !paths.is_empty()
+            && !paths.iter().any(|path| {
+                name.to_ascii_lowercase()
+                    .starts_with(&path.replace('\\', "/").to_ascii_lowercase())
+            })
+        {
+            continue;
+        }
+
+        // Entries must stay inside of the output directory
+        let relative = PathBuf::from(&name);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            eprintln!("warning: skipping {}", entry.name);
+            continue;
+        }
+        let output = directory.join(relative);
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&output, pbo.read(&mut input, entry)?)?;
+        println!("{}", output.display());
+    }
+    Ok(())
+}
+
+fn convert(directory: &Path, inputs: &[&str]) -> Result<()> {
+    let mut converter = AssetConverter::new(
+        directory,
+        P3dSettings {
+            validation: P3dValidation::Lenient,
+            ..Default::default()
+        },
+    );
+    for input in inputs {
+        converter.convert(Path::new(input))?;
+    }
+    let (manifest, failures) = converter.finish()?;
+    for (path, error) in &failures {
+        eprintln!("warning: failed to convert {path}: {error:#}");
+    }
+    println!(
+        "converted {} files, {} failed",
+        manifest.assets.len(),
+        failures.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,413 @@
+use std::io::Read;
+
+use anyhow::{bail, Result};
+use byteorder::{LittleEndian, ReadBytesExt};
+use thiserror::Error;
+
+/// Arrays below this size are never compressed.
+const COMPRESSED_ARRAY_THRESHOLD: usize = 1024;
+
+const LZSS_WINDOW_SIZE: usize = 0x1000;
+const LZSS_MINIMUM_MATCH: usize = 3;
+const LZSS_MAXIMUM_MATCH: usize = 0xF + LZSS_MINIMUM_MATCH;
+
+/// Upper bound of bytes and elements allocated up front, as sizes are not trusted.
+const MAXIMUM_PREALLOCATION: usize = 0x10000;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+enum CompressionError {
+    #[error("invalid back-reference at {0}")]
+    InvalidBackReference(usize),
+    #[error("decompressed size mismatch, expected {expected} but got {actual}")]
+    SizeMismatch { expected: usize, actual: usize },
+    #[error("checksum mismatch, expected {expected:#010x} but got {actual:#010x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+    #[error("array of {0} elements is too large")]
+    ArrayTooLarge(usize),
+}
+
+/// Checksum of LZSS compressed data, the wrapping sum of all decompressed bytes.
+pub fn lzss_checksum(data: &[u8]) -> u32 {
+    data.iter()
+        .fold(0u32, |checksum, &value| checksum.wrapping_add(value as u32))
+}
+
+/// Decompresses Bohemia LZSS data of a known size, followed by its checksum.
+pub fn lzss_decompress<R: Read>(input: &mut R, size: usize) -> Result<Vec<u8>> {
+    let output = lzss_decompress_unchecked(input, size)?;
+
+    let expected = input.read_u32::<LittleEndian>()?;
+    let actual = lzss_checksum(&output);
+    if expected != actual {
+        bail!(CompressionError::ChecksumMismatch { expected, actual })
+    }
+
+    Ok(output)
+}
+
+/// Decompresses Bohemia LZSS data of a known size, without reading the checksum.
+pub fn lzss_decompress_unchecked<R: Read>(input: &mut R, size: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(size.min(MAXIMUM_PREALLOCATION));
+    while output.len() < size {
+        let flags = input.read_u8()?;
+        for bit in 0..8 {
+            if output.len() >= size {
+                break;
+            }
+
+            // Set bits are literals, cleared bits are back-references
+            if flags & (1 << bit) != 0 {
+                output.push(input.read_u8()?);
+                continue;
+            }
+
+            let low = input.read_u8()? as usize;
+            let high = input.read_u8()? as usize;
+            let offset = low | (high & 0xF0) << 4;
+            let length = (high & 0x0F) + LZSS_MINIMUM_MATCH;
+            if offset == 0 {
+                bail!(CompressionError::InvalidBackReference(output.len()))
+            }
+
+            // Bytes before the start of the output are spaces
+            for _ in 0..length.min(size - output.len()) {
+                let value = match output.len().checked_sub(offset) {
+                    Some(position) => output[position],
+                    None => b' ',
+                };
+                output.push(value);
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// Compresses data with Bohemia LZSS, followed by its checksum.
+pub fn lzss_compress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len() + data.len() / 8 + 5);
+    let mut position = 0;
+    while position < data.len() {
+        let flags_position = output.len();
+        output.push(0);
+        for bit in 0..8 {
+            if position >= data.len() {
+                break;
+            }
+
+            // Greedy search for the longest match inside of the window
+            let window_start = position.saturating_sub(LZSS_WINDOW_SIZE - 1);
+            let maximum_length = LZSS_MAXIMUM_MATCH.min(data.len() - position);
+            let mut best_length = 0;
+            let mut best_offset = 0;
+            for start in (window_start..position).rev() {
+                let length = (0..maximum_length)
+                    .take_while(|&i| data[start + i] == data[position + i])
+                    .count();
+                if length > best_length {
+                    best_length = length;
+                    best_offset = position - start;
+                    if length == maximum_length {
+                        break;
+                    }
+                }
+            }
+
+            if best_length >= LZSS_MINIMUM_MATCH {
+                output.push(best_offset as u8);
+                output.push(
+                    ((best_offset >> 4) & 0xF0) as u8 | (best_length - LZSS_MINIMUM_MATCH) as u8,
+                );
+                position += best_length;
+            } else {
+                output[flags_position] |= 1 << bit;
+                output.push(data[position]);
+                position += 1;
+            }
+        }
+    }
+    output.extend_from_slice(&lzss_checksum(data).to_le_bytes());
+
+    output
+}
+
+/// Decompresses LZO1X data of a known size, where the compressed size is not known, like in
+/// ODOL. Data of a known compressed size is decompressed with `minilzo`.
+pub fn lzo_decompress<R: Read>(input: &mut R, size: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(size.min(MAXIMUM_PREALLOCATION));
+
+    // Number of literals following the last match, 4 stands for a literal run
+    let mut state;
+    let mut instruction = input.read_u8()? as usize;
+    if instruction > 17 {
+        let length = instruction - 17;
+        copy_literals(input, &mut output, length)?;
+        state = length.min(4);
+        instruction = input.read_u8()? as usize;
+    } else {
+        state = 0;
+    }
+
+    loop {
+        let (distance, length, next_state) = if instruction >= 64 {
+            let distance = 1 + ((instruction >> 2) & 7) + ((input.read_u8()? as usize) << 3);
+            (distance, (instruction >> 5) + 1, instruction & 3)
+        } else if instruction >= 32 {
+            let mut length = instruction & 31;
+            if length == 0 {
+                length = 31 + read_lzo_length(input)?;
+            }
+            let value = input.read_u16::<LittleEndian>()? as usize;
+            (1 + (value >> 2), length + 2, value & 3)
+        } else if instruction >= 16 {
+            let mut length = instruction & 7;
+            if length == 0 {
+                length = 7 + read_lzo_length(input)?;
+            }
+            let value = input.read_u16::<LittleEndian>()? as usize;
+            let distance = ((instruction & 8) << 11) + (value >> 2);
+
+            // Distance of zero marks the end of the stream
+            if distance == 0 {
+                break;
+            }
+            (distance + 0x4000, length + 2, value & 3)
+        } else if state == 0 {
+            let mut length = instruction;
+            if length == 0 {
+                length = 15 + read_lzo_length(input)?;
+            }
+            copy_literals(input, &mut output, length + 3)?;
+            state = 4;
+            instruction = input.read_u8()? as usize;
+            continue;
+        } else {
+            let distance = 1 + (instruction >> 2) + ((input.read_u8()? as usize) << 2);
+            if state == 4 {
+                (distance + 0x800, 3, instruction & 3)
+            } else {
+                (distance, 2, instruction & 3)
+            }
+        };
+
+        if distance > output.len() {
+            bail!(CompressionError::InvalidBackReference(output.len()))
+        }
+        for _ in 0..length {
+            output.push(output[output.len() - distance]);
+        }
+        copy_literals(input, &mut output, next_state)?;
+        state = next_state;
+
+        if output.len() > size {
+            break;
+        }
+        instruction = input.read_u8()? as usize;
+    }
+
+    if output.len() != size {
+        bail!(CompressionError::SizeMismatch {
+            expected: size,
+            actual: output.len()
+        })
+    }
+
+    Ok(output)
+}
+
+fn read_lzo_length<R: Read>(input: &mut R) -> Result<usize> {
+    let mut length = 0;
+    loop {
+        match input.read_u8()? {
+            0 => length += 255,
+            value => return Ok(length + value as usize),
+        }
+    }
+}
+
+fn copy_literals<R: Read>(input: &mut R, output: &mut Vec<u8>, length: usize) -> Result<()> {
+    let start = output.len();
+    output.resize(start + length, 0);
+    input.read_exact(&mut output[start..])?;
+    Ok(())
+}
+
+/// Reads the data of a compressed array with a known decompressed size.
+///
+/// Data below 1024 bytes is stored as is, otherwise it is compressed with LZSS before version 44
+/// and with LZO since, where version 64 and above prefix it with a flag whether it is compressed
+/// at all.
+pub fn read_compressed<R: Read>(input: &mut R, size: usize, version: u32) -> Result<Vec<u8>> {
+    let compressed = if version >= 64 {
+        input.read_u8()? != 0
+    } else {
+        size >= COMPRESSED_ARRAY_THRESHOLD
+    };
+
+    if !compressed {
+        // Grows with the input instead of allocating the whole size up front
+        let mut data = Vec::with_capacity(size.min(MAXIMUM_PREALLOCATION));
+        input.take(size as u64).read_to_end(&mut data)?;
+        if data.len() != size {
+            bail!(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))
+        }
+        Ok(data)
+    } else if version >= 44 {
+        lzo_decompress(input, size)
+    } else {
+        lzss_decompress(input, size)
+    }
+}
+
+/// Reads a compressed array prefixed with its element count, elements are read from the
+/// decompressed data by the given function.
+pub fn read_compressed_array<R: Read, T>(
+    input: &mut R,
+    version: u32,
+    element_size: usize,
+    mut read_element: impl FnMut(&mut &[u8]) -> std::io::Result<T>,
+) -> Result<Vec<T>> {
+    let count = input.read_u32::<LittleEndian>()? as usize;
+    let Some(size) = count.checked_mul(element_size) else {
+        bail!(CompressionError::ArrayTooLarge(count))
+    };
+    let data = read_compressed(input, size, version)?;
+
+    let mut elements = Vec::with_capacity(count.min(MAXIMUM_PREALLOCATION));
+    let mut data = data.as_slice();
+    for _ in 0..count {
+        elements.push(read_element(&mut data)?);
+    }
+
+    Ok(elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lzss_literals() {
+        let data = [
+            0xFF, b'a', b'b', b'c', b'd', b'e', b'f', b'g', b'h', 0x24, 0x03, 0, 0, 0,
+        ];
+        assert_eq!(
+            lzss_decompress(&mut data.as_slice(), 8).unwrap(),
+            b"abcdefgh"
+        );
+    }
+
+    #[test]
+    fn lzss_back_reference() {
+        // Three literals, followed by a back-reference of offset 3 and length 6
+        let mut data = vec![0b0111, b'a', b'b', b'c', 0x03, 0x03];
+        data.extend_from_slice(&lzss_checksum(b"abcabcabc").to_le_bytes());
+        assert_eq!(
+            lzss_decompress(&mut data.as_slice(), 9).unwrap(),
+            b"abcabcabc"
+        );
+    }
+
+    #[test]
+    fn lzss_leading_spaces() {
+        // Back-reference before the start of the output
+        let mut data = vec![0b10, 0x04, 0x00, b'x'];
+        data.extend_from_slice(&lzss_checksum(b"   x").to_le_bytes());
+        assert_eq!(lzss_decompress(&mut data.as_slice(), 4).unwrap(), b"   x");
+    }
+
+    #[test]
+    fn lzss_checksum_mismatch() {
+        let data = [0x01, b'a', 0x62, 0, 0, 0];
+        let error = lzss_decompress(&mut data.as_slice(), 1).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<CompressionError>(),
+            Some(&CompressionError::ChecksumMismatch {
+                expected: 0x62,
+                actual: 0x61
+            })
+        );
+    }
+
+    #[test]
+    fn lzss_round_trip() {
+        let data: Vec<u8> = b"class CfgPatches { class vixen { units[] = {}; }; };"
+            .iter()
+            .cycle()
+            .take(10000)
+            .copied()
+            .chain((0..=255).cycle().take(5000))
+            .collect();
+        let compressed = lzss_compress(&data);
+        assert!(compressed.len() < data.len());
+        assert_eq!(
+            lzss_decompress(&mut compressed.as_slice(), data.len()).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn lzo_literals() {
+        let data = [20, b'a', b'b', b'c', 0x11, 0, 0];
+        assert_eq!(lzo_decompress(&mut data.as_slice(), 3).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn lzo_back_reference() {
+        let data = [21, b'a', b'b', b'c', b'd', 236, 0, 0x11, 0, 0];
+        assert_eq!(
+            lzo_decompress(&mut data.as_slice(), 12).unwrap(),
+            b"abcdabcdabcd"
+        );
+    }
+
+    #[test]
+    fn lzo_size_mismatch() {
+        let data = [20, b'a', b'b', b'c', 0x11, 0, 0];
+        let error = lzo_decompress(&mut data.as_slice(), 4).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<CompressionError>(),
+            Some(&CompressionError::SizeMismatch {
+                expected: 4,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn compressed_array() {
+        // Uncompressed below the threshold
+        let data = [2, 0, 0, 0, 1, 0, 2, 0];
+        assert_eq!(
+            read_compressed_array(&mut data.as_slice(), 40, 2, |input| input
+                .read_u16::<LittleEndian>())
+            .unwrap(),
+            [1, 2]
+        );
+
+        // LZSS before version 44
+        let values: Vec<u8> = (0..1024).map(|i| (i % 7) as u8).collect();
+        let mut data = 1024u32.to_le_bytes().to_vec();
+        data.extend(lzss_compress(&values));
+        assert_eq!(
+            read_compressed_array(&mut data.as_slice(), 40, 1, |input| input.read_u8()).unwrap(),
+            values
+        );
+
+        // Flag since version 64
+        let data = [3, 0, 0, 0, 0, 7, 8, 9];
+        assert_eq!(
+            read_compressed_array(&mut data.as_slice(), 64, 1, |input| input.read_u8()).unwrap(),
+            [7, 8, 9]
+        );
+        let data = [3, 0, 0, 0, 0, 7, 8];
+        assert!(
+            read_compressed_array(&mut data.as_slice(), 64, 1, |input| input.read_u8()).is_err()
+        );
+        let data = [3, 0, 0, 0, 1, 20, b'a', b'b', b'c', 0x11, 0, 0];
+        assert_eq!(
+            read_compressed_array(&mut data.as_slice(), 64, 1, |input| input.read_u8()).unwrap(),
+            b"abc"
+        );
+    }
+}
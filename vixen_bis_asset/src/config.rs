@@ -0,0 +1,874 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    io::{Cursor, Read, Seek, SeekFrom},
+};
+
+use anyhow::{bail, Result};
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    reflect::TypeUuid,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use thiserror::Error;
+
+use crate::p3d::read_asciiz;
+
+/// Maximum nesting of rap classes and arrays.
+const MAXIMUM_RAP_DEPTH: usize = 64;
+
+/// Maximum length of inheritance chains, which may be cyclic.
+const MAXIMUM_INHERITANCE_DEPTH: usize = 64;
+
+/// Loader of text and rap-binarized configs, like config.cpp, model.cfg and RVMATs.
+#[derive(Default)]
+pub struct ConfigLoader;
+
+impl AssetLoader for ConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(ConfigClass::from_bytes(bytes)?));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["cpp", "hpp", "cfg", "rvmat", "bisurf"]
+    }
+}
+
+/// Bohemia config class, the syntax used by config.cpp, model.cfg, mission.sqm and others.
+#[derive(TypeUuid, Clone, Debug, Default, PartialEq)]
+#[uuid = "4a6d2e91-c3b5-4f78-8d1e-9b2f5a7c0e63"]
+pub struct ConfigClass {
+    pub parent: Option<String>,
+    pub entries: Vec<(String, ConfigEntry)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigEntry {
+    Class(ConfigClass),
+    /// Forward declaration, `class Name;`
+    Extern,
+    /// `delete Name;`
+    Delete,
+    Value(ConfigValue),
+    /// Array expansion, `name[] += {...};`
+    Expansion(Vec<ConfigValue>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigValue {
+    String(String),
+    Float(f32),
+    Int(i32),
+    Array(Vec<ConfigValue>),
+}
+
+impl ConfigValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Self::Float(value) => Some(*value),
+            Self::Int(value) => Some(*value as f32),
+            // Numbers can also be given as strings, e.g. from macros
+            Self::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            Self::Int(value) => Some(*value),
+            Self::Float(value) => Some(*value as i32),
+            Self::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[ConfigValue]> {
+        match self {
+            Self::Array(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(value) => write!(f, "\"{}\"", value.replace('"', "\"\"")),
+            Self::Float(value) => write!(f, "{value:?}"),
+            Self::Int(value) => write!(f, "{value}"),
+            Self::Array(values) => {
+                write!(f, "{{")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Writes the config in text syntax.
+impl fmt::Display for ConfigClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_class_body(f, self, 0)
+    }
+}
+
+fn write_class_body(f: &mut fmt::Formatter<'_>, class: &ConfigClass, depth: usize) -> fmt::Result {
+    let indent = "    ".repeat(depth);
+    for (name, entry) in &class.entries {
+        match entry {
+            ConfigEntry::Class(class) => {
+                write!(f, "{indent}class {name}")?;
+                if let Some(parent) = &class.parent {
+                    write!(f, ": {parent}")?;
+                }
+                writeln!(f, " {{")?;
+                write_class_body(f, class, depth + 1)?;
+                writeln!(f, "{indent}}};")?;
+            }
+            ConfigEntry::Extern => writeln!(f, "{indent}class {name};")?,
+            ConfigEntry::Delete => writeln!(f, "{indent}delete {name};")?,
+            ConfigEntry::Value(value @ ConfigValue::Array(_)) => {
+                writeln!(f, "{indent}{name}[] = {value};")?
+            }
+            ConfigEntry::Value(value) => writeln!(f, "{indent}{name} = {value};")?,
+            ConfigEntry::Expansion(values) => writeln!(
+                f,
+                "{indent}{name}[] += {};",
+                ConfigValue::Array(values.clone())
+            )?,
+        }
+    }
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+enum ConfigError {
+    #[error("unexpected character '{0}' at line {1}")]
+    UnexpectedCharacter(char, usize),
+    #[error("unexpected end of file")]
+    UnexpectedEndOfFile,
+    #[error("invalid rap magic")]
+    InvalidRapMagic,
+    #[error("unknown rap entry type {0} at {1}")]
+    UnknownRapEntry(u8, u64),
+    #[error("rap nested too deeply")]
+    RapTooDeep,
+    #[error("invalid rap class offset {0} at {1}")]
+    InvalidRapOffset(u32, u64),
+}
+
+impl ConfigClass {
+    /// Parses a text config, preprocessor directives are skipped.
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut parser = Parser {
+            input: input.as_bytes(),
+            position: 0,
+            line: 1,
+        };
+        let mut class = Self::default();
+        parser.class_body(&mut class, false)?;
+
+        Ok(class)
+    }
+
+    /// Reads a config which is either text or rap-binarized.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.starts_with(b"\0raP") {
+            Self::read_rap(&mut Cursor::new(bytes))
+        } else {
+            Self::parse(&String::from_utf8_lossy(bytes))
+        }
+    }
+
+    /// Reads a rap-binarized config.
+    pub fn read_rap<R: Read + Seek>(input: &mut R) -> Result<Self> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != b"\0raP" {
+            bail!(ConfigError::InvalidRapMagic)
+        }
+        let _always_0 = input.read_u32::<LittleEndian>()?;
+        let _always_8 = input.read_u32::<LittleEndian>()?;
+        let _enum_offset = input.read_u32::<LittleEndian>()?;
+
+        read_rap_class_body(input, 0, &mut HashSet::new())
+    }
+
+    /// Returns an own entry, names are case-insensitive.
+    pub fn entry(&self, name: &str) -> Option<&ConfigEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(entry_name, _)| entry_name.eq_ignore_ascii_case(name))
+            .map(|(_, entry)| entry)
+    }
+}
+
+/// Reads a class body, bodies are stored after the entry table referencing them, and each one is
+/// only referenced once.
+fn read_rap_class_body<R: Read + Seek>(
+    input: &mut R,
+    depth: usize,
+    visited: &mut HashSet<u32>,
+) -> Result<ConfigClass> {
+    if depth > MAXIMUM_RAP_DEPTH {
+        bail!(ConfigError::RapTooDeep)
+    }
+
+    let parent = read_asciiz(input)?;
+    let mut class = ConfigClass {
+        parent: (!parent.is_empty()).then_some(parent),
+        entries: Vec::new(),
+    };
+    for _ in 0..read_compressed_int(input)? {
+        let position = input.stream_position()?;
+        let entry_type = input.read_u8()?;
+        let (name, entry) = match entry_type {
+            0 => {
+                let name = read_asciiz(input)?;
+                let offset = input.read_u32::<LittleEndian>()?;
+
+                // Bodies are stored elsewhere, continue after the offset
+                let next = input.stream_position()?;
+                if (offset as u64) < next || !visited.insert(offset) {
+                    bail!(ConfigError::InvalidRapOffset(offset, position))
+                }
+                input.seek(SeekFrom::Start(offset as u64))?;
+                let body = read_rap_class_body(input, depth + 1, visited)?;
+                input.seek(SeekFrom::Start(next))?;
+                (name, ConfigEntry::Class(body))
+            }
+            1 => {
+                let value_type = input.read_u8()?;
+                let name = read_asciiz(input)?;
+                let value = read_rap_value(input, value_type, position, depth)?;
+                (name, ConfigEntry::Value(value))
+            }
+            2 => {
+                let name = read_asciiz(input)?;
+                let values = read_rap_array(input, depth + 1)?;
+                (name, ConfigEntry::Value(ConfigValue::Array(values)))
+            }
+            3 => (read_asciiz(input)?, ConfigEntry::Extern),
+            4 => (read_asciiz(input)?, ConfigEntry::Delete),
+            5 => {
+                let _flags = input.read_u32::<LittleEndian>()?;
+                let name = read_asciiz(input)?;
+                let values = read_rap_array(input, depth + 1)?;
+                (name, ConfigEntry::Expansion(values))
+            }
+            _ => bail!(ConfigError::UnknownRapEntry(entry_type, position)),
+        };
+        class.entries.push((name, entry));
+    }
+
+    Ok(class)
+}
+
+fn read_rap_array<R: Read + Seek>(input: &mut R, depth: usize) -> Result<Vec<ConfigValue>> {
+    if depth > MAXIMUM_RAP_DEPTH {
+        bail!(ConfigError::RapTooDeep)
+    }
+
+    let mut values = Vec::new();
+    for _ in 0..read_compressed_int(input)? {
+        let position = input.stream_position()?;
+        let value_type = input.read_u8()?;
+        values.push(read_rap_value(input, value_type, position, depth)?);
+    }
+
+    Ok(values)
+}
+
+fn read_rap_value<R: Read + Seek>(
+    input: &mut R,
+    value_type: u8,
+    position: u64,
+    depth: usize,
+) -> Result<ConfigValue> {
+    Ok(match value_type {
+        // Variables are kept as their name
+        0 | 4 => ConfigValue::String(read_asciiz(input)?),
+        1 => ConfigValue::Float(input.read_f32::<LittleEndian>()?),
+        2 => ConfigValue::Int(input.read_i32::<LittleEndian>()?),
+        3 => ConfigValue::Array(read_rap_array(input, depth + 1)?),
+        6 => ConfigValue::Int(input.read_i64::<LittleEndian>()? as i32),
+        _ => bail!(ConfigError::UnknownRapEntry(value_type, position)),
+    })
+}
+
+/// Reads a little-endian base 128 integer.
+fn read_compressed_int<R: Read>(input: &mut R) -> Result<u32> {
+    let mut value = 0;
+    for shift in (0..32).step_by(7) {
+        let byte = input.read_u8()?;
+        value |= ((byte & 0x7F) as u32) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    Ok(value)
+}
+
+/// Inheritance aware view into a config class.
+#[derive(Clone, Debug)]
+pub struct ConfigCursor<'a> {
+    name: &'a str,
+    class: &'a ConfigClass,
+    scope: Option<Box<ConfigCursor<'a>>>,
+}
+
+impl<'a> ConfigCursor<'a> {
+    pub fn new(class: &'a ConfigClass) -> Self {
+        Self {
+            name: "",
+            class,
+            scope: None,
+        }
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn get(&self) -> &'a ConfigClass {
+        self.class
+    }
+
+    /// Returns the base class, which is looked up in the enclosing classes.
+    pub fn base(&self) -> Option<ConfigCursor<'a>> {
+        let parent = self.class.parent.as_ref()?;
+        let mut scope = self.scope.as_deref();
+        while let Some(current) = scope {
+            if let Some(base) = current.find_class(parent, self.class) {
+                return Some(base);
+            }
+            scope = current.scope.as_deref();
+        }
+
+        None
+    }
+
+    /// Returns a class, either own or inherited.
+    pub fn class(&self, name: &str) -> Option<ConfigCursor<'a>> {
+        self.find_class(name, std::ptr::null())
+    }
+
+    /// Returns a value, either own or inherited.
+    pub fn value(&self, name: &str) -> Option<&'a ConfigValue> {
+        match self.find(name, std::ptr::null())?.2 {
+            ConfigEntry::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns all classes, own ones first followed by inherited ones which are not overridden.
+    pub fn classes(&self) -> Vec<ConfigCursor<'a>> {
+        let mut classes: Vec<ConfigCursor<'a>> = Vec::new();
+        let mut current = Some(self.clone());
+        let mut depth = 0;
+        while let Some(class) = current {
+            let own = class.class;
+            for (name, entry) in &own.entries {
+                if classes
+                    .iter()
+                    .any(|other| other.name.eq_ignore_ascii_case(name))
+                {
+                    continue;
+                }
+                if let ConfigEntry::Class(child) = entry {
+                    classes.push(ConfigCursor {
+                        name,
+                        class: child,
+                        scope: Some(Box::new(class.clone())),
+                    });
+                }
+            }
+
+            depth += 1;
+            if depth > MAXIMUM_INHERITANCE_DEPTH {
+                break;
+            }
+            current = class.base();
+        }
+
+        classes
+    }
+
+    fn find_class(&self, name: &str, exclude: *const ConfigClass) -> Option<ConfigCursor<'a>> {
+        let (owner, name, entry) = self.find(name, exclude)?;
+        match entry {
+            ConfigEntry::Class(class) => Some(ConfigCursor {
+                name,
+                class,
+                scope: Some(Box::new(owner)),
+            }),
+            _ => None,
+        }
+    }
+
+    fn find(
+        &self,
+        name: &str,
+        exclude: *const ConfigClass,
+    ) -> Option<(ConfigCursor<'a>, &'a str, &'a ConfigEntry)> {
+        let mut current = self.clone();
+        for _ in 0..MAXIMUM_INHERITANCE_DEPTH {
+            let own = current.class;
+            if let Some((entry_name, entry)) = own.entries.iter().rev().find(|(entry_name, entry)| {
+                entry_name.eq_ignore_ascii_case(name)
+                    && !matches!(entry, ConfigEntry::Class(class) if std::ptr::eq(class, exclude))
+            }) {
+                return Some((current, entry_name, entry));
+            }
+            current = current.base()?;
+        }
+
+        None
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Result<u8> {
+        let value = self.peek().ok_or(ConfigError::UnexpectedEndOfFile)?;
+        self.position += 1;
+        if value == b'\n' {
+            self.line += 1;
+        }
+
+        Ok(value)
+    }
+
+    fn eat(&mut self, expected: u8) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        let value = self.next()?;
+        if value != expected {
+            bail!(ConfigError::UnexpectedCharacter(value as char, self.line))
+        }
+
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) -> Result<()> {
+        while let Some(value) = self.peek() {
+            match value {
+                b' ' | b'\t' | b'\r' | b'\n' => {
+                    self.next()?;
+                }
+                b'/' if self.input.get(self.position + 1) == Some(&b'/') => {
+                    while !matches!(self.peek(), None | Some(b'\n')) {
+                        self.next()?;
+                    }
+                }
+                b'/' if self.input.get(self.position + 1) == Some(&b'*') => {
+                    self.position += 2;
+                    while !self.input[self.position..].starts_with(b"*/") {
+                        self.next()?;
+                    }
+                    self.position += 2;
+                }
+                // Skip preprocessor directives, including line continuations
+                b'#' => loop {
+                    match self.peek() {
+                        None | Some(b'\n') => break,
+                        Some(b'\\') => {
+                            self.next()?;
+                            if self.peek() == Some(b'\r') {
+                                self.next()?;
+                            }
+                            if self.peek() == Some(b'\n') {
+                                self.next()?;
+                            }
+                        }
+                        _ => {
+                            self.next()?;
+                        }
+                    }
+                },
+                _ => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn identifier(&mut self) -> Result<String> {
+        let start = self.position;
+        while matches!(self.peek(), Some(value) if value.is_ascii_alphanumeric() || value == b'_') {
+            self.position += 1;
+        }
+        if start == self.position {
+            let value = self.next()?;
+            bail!(ConfigError::UnexpectedCharacter(value as char, self.line))
+        }
+
+        Ok(String::from_utf8_lossy(&self.input[start..self.position]).into_owned())
+    }
+
+    fn class_body(&mut self, class: &mut ConfigClass, nested: bool) -> Result<()> {
+        loop {
+            self.skip_whitespace()?;
+            match self.peek() {
+                None if !nested => return Ok(()),
+                None => bail!(ConfigError::UnexpectedEndOfFile),
+                Some(b'}') if nested => {
+                    self.next()?;
+                    self.skip_whitespace()?;
+                    self.eat(b';');
+                    return Ok(());
+                }
+                Some(b';') => {
+                    self.next()?;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let name = self.identifier()?;
+            self.skip_whitespace()?;
+            match name.as_str() {
+                "class" => {
+                    let name = self.identifier()?;
+                    self.skip_whitespace()?;
+                    if self.eat(b';') {
+                        class.entries.push((name, ConfigEntry::Extern));
+                        continue;
+                    }
+
+                    let mut child = ConfigClass::default();
+                    if self.eat(b':') {
+                        self.skip_whitespace()?;
+                        child.parent = Some(self.identifier()?);
+                        self.skip_whitespace()?;
+                    }
+                    self.expect(b'{')?;
+                    self.class_body(&mut child, true)?;
+                    class.entries.push((name, ConfigEntry::Class(child)));
+                }
+                "delete" => {
+                    let name = self.identifier()?;
+                    self.skip_whitespace()?;
+                    self.expect(b';')?;
+                    class.entries.push((name, ConfigEntry::Delete));
+                }
+                "enum" => {
+                    // Enums are only relevant for the preprocessor, skip them
+                    while self.next()? != b'}' {}
+                    self.skip_whitespace()?;
+                    self.eat(b';');
+                }
+                _ => {
+                    if self.eat(b'[') {
+                        self.skip_whitespace()?;
+                        self.expect(b']')?;
+                        self.skip_whitespace()?;
+                        let expansion = self.eat(b'+');
+                        self.expect(b'=')?;
+                        self.skip_whitespace()?;
+                        let array = self.array()?;
+                        self.skip_whitespace()?;
+                        self.expect(b';')?;
+                        class.entries.push((
+                            name,
+                            if expansion {
+                                ConfigEntry::Expansion(array)
+                            } else {
+                                ConfigEntry::Value(ConfigValue::Array(array))
+                            },
+                        ));
+                    } else {
+                        self.expect(b'=')?;
+                        self.skip_whitespace()?;
+                        let value = self.scalar(b";")?;
+                        self.skip_whitespace()?;
+                        self.expect(b';')?;
+                        class.entries.push((name, ConfigEntry::Value(value)));
+                    }
+                }
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Vec<ConfigValue>> {
+        self.expect(b'{')?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace()?;
+            if self.eat(b'}') {
+                break;
+            }
+            if self.peek() == Some(b'{') {
+                values.push(ConfigValue::Array(self.array()?));
+            } else {
+                values.push(self.scalar(b",}")?);
+            }
+            self.skip_whitespace()?;
+            if !self.eat(b',') {
+                self.expect(b'}')?;
+                break;
+            }
+        }
+
+        Ok(values)
+    }
+
+    fn scalar(&mut self, terminators: &[u8]) -> Result<ConfigValue> {
+        // Quoted string, quotes are escaped by doubling them
+        if self.eat(b'"') {
+            let mut value = Vec::new();
+            loop {
+                match self.next()? {
+                    b'"' if self.eat(b'"') => value.push(b'"'),
+                    b'"' => break,
+                    other => value.push(other),
+                }
+            }
+
+            return Ok(ConfigValue::String(
+                String::from_utf8_lossy(&value).into_owned(),
+            ));
+        }
+
+        // Unquoted values are either numbers or strings
+        let start = self.position;
+        while matches!(self.peek(), Some(value) if !terminators.contains(&value) && value != b'\n')
+        {
+            self.next()?;
+        }
+        let value = String::from_utf8_lossy(&self.input[start..self.position]);
+        let value = value.trim();
+
+        Ok(if let Ok(value) = value.parse() {
+            ConfigValue::Int(value)
+        } else if let Some(Ok(value)) = value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+            .map(|value| u32::from_str_radix(value, 16))
+        {
+            ConfigValue::Int(value as i32)
+        } else if let (Some(b'0'..=b'9' | b'-' | b'+' | b'.'), Ok(value)) =
+            (value.bytes().next(), value.parse())
+        {
+            ConfigValue::Float(value)
+        } else {
+            ConfigValue::String(value.to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::WriteBytesExt;
+
+    use super::*;
+
+    #[test]
+    fn inheritance() {
+        let config = ConfigClass::parse(
+            r#"
+            class Base;
+            class Vehicle {
+                speed = 10;
+                class Turret { rotation = 1; };
+            };
+            class Car: Vehicle {
+                class Turret: Turret { elevation = 2; };
+                delete Wheels;
+            };
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.entry("base"), Some(&ConfigEntry::Extern));
+
+        let root = ConfigCursor::new(&config);
+        let car = root.class("Car").unwrap();
+        assert_eq!(car.base().unwrap().name(), "Vehicle");
+        assert_eq!(car.value("speed"), Some(&ConfigValue::Int(10)));
+        assert_eq!(car.get().entry("wheels"), Some(&ConfigEntry::Delete));
+
+        // Classes inheriting from their namesake in the base class
+        let turret = car.class("Turret").unwrap();
+        assert_eq!(turret.value("elevation"), Some(&ConfigValue::Int(2)));
+        assert_eq!(turret.value("rotation"), Some(&ConfigValue::Int(1)));
+    }
+
+    #[test]
+    fn arrays() {
+        let config = ConfigClass::parse(
+            r#"
+            empty[] = {};
+            nested[] = {1, {2, "a"}, {}};
+            nested[] += {3};
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.entries[0].1,
+            ConfigEntry::Value(ConfigValue::Array(vec![]))
+        );
+        assert_eq!(
+            config.entries[1].1,
+            ConfigEntry::Value(ConfigValue::Array(vec![
+                ConfigValue::Int(1),
+                ConfigValue::Array(vec![
+                    ConfigValue::Int(2),
+                    ConfigValue::String("a".to_string())
+                ]),
+                ConfigValue::Array(vec![]),
+            ]))
+        );
+        assert_eq!(
+            config.entry("nested"),
+            Some(&ConfigEntry::Expansion(vec![ConfigValue::Int(3)]))
+        );
+    }
+
+    #[test]
+    fn scalars() {
+        let config = ConfigClass::parse(
+            r#"
+            quoted = "say ""hi""";
+            unquoted = some text;
+            int = -12;
+            hex = 0xFF;
+            float = 1.5;
+            exponent = 1e-3;
+            "#,
+        )
+        .unwrap();
+        let value = |name| match config.entry(name) {
+            Some(ConfigEntry::Value(value)) => value.clone(),
+            _ => panic!("missing value {name}"),
+        };
+        assert_eq!(
+            value("quoted"),
+            ConfigValue::String("say \"hi\"".to_string())
+        );
+        assert_eq!(
+            value("unquoted"),
+            ConfigValue::String("some text".to_string())
+        );
+        assert_eq!(value("int"), ConfigValue::Int(-12));
+        assert_eq!(value("hex"), ConfigValue::Int(0xFF));
+        assert_eq!(value("float"), ConfigValue::Float(1.5));
+        assert_eq!(value("exponent"), ConfigValue::Float(1e-3));
+
+        // Quotes are escaped when written
+        assert_eq!(value("quoted").to_string(), r#""say ""hi""""#);
+    }
+
+    fn write_asciiz(output: &mut Vec<u8>, value: &str) {
+        output.extend_from_slice(value.as_bytes());
+        output.push(0);
+    }
+
+    #[test]
+    fn rap() {
+        let text = ConfigClass::parse(
+            r#"
+            class Base;
+            class Vehicle {
+                name = "car";
+                mass = 1.5;
+                wheels[] = {4, {"front", "rear"}};
+            };
+            class Car: Vehicle {
+                wheels[] += {2};
+                delete Base;
+            };
+            "#,
+        )
+        .unwrap();
+
+        let mut rap = b"\0raP".to_vec();
+        rap.extend_from_slice(&[0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0]);
+        // Root class, bodies are stored after the entry table
+        rap.push(0);
+        rap.push(3);
+        rap.push(3);
+        write_asciiz(&mut rap, "Base");
+        rap.push(0);
+        write_asciiz(&mut rap, "Vehicle");
+        let vehicle_offset = rap.len();
+        rap.extend_from_slice(&[0; 4]);
+        rap.push(0);
+        write_asciiz(&mut rap, "Car");
+        let car_offset = rap.len();
+        rap.extend_from_slice(&[0; 4]);
+
+        let offset = rap.len() as u32;
+        rap[vehicle_offset..vehicle_offset + 4].copy_from_slice(&offset.to_le_bytes());
+        rap.push(0);
+        rap.push(3);
+        rap.extend_from_slice(&[1, 0]);
+        write_asciiz(&mut rap, "name");
+        write_asciiz(&mut rap, "car");
+        rap.extend_from_slice(&[1, 1]);
+        write_asciiz(&mut rap, "mass");
+        rap.write_f32::<LittleEndian>(1.5).unwrap();
+        rap.push(2);
+        write_asciiz(&mut rap, "wheels");
+        rap.extend_from_slice(&[2, 2]);
+        rap.write_i32::<LittleEndian>(4).unwrap();
+        rap.extend_from_slice(&[3, 2, 0]);
+        write_asciiz(&mut rap, "front");
+        rap.push(0);
+        write_asciiz(&mut rap, "rear");
+
+        let offset = rap.len() as u32;
+        rap[car_offset..car_offset + 4].copy_from_slice(&offset.to_le_bytes());
+        write_asciiz(&mut rap, "Vehicle");
+        rap.push(2);
+        rap.push(5);
+        rap.write_u32::<LittleEndian>(1).unwrap();
+        write_asciiz(&mut rap, "wheels");
+        rap.extend_from_slice(&[1, 2]);
+        rap.write_i32::<LittleEndian>(2).unwrap();
+        rap.push(4);
+        write_asciiz(&mut rap, "Base");
+
+        assert_eq!(ConfigClass::from_bytes(&rap).unwrap(), text);
+
+        // Class bodies before their entry are rejected
+        rap[car_offset..car_offset + 4].copy_from_slice(&16u32.to_le_bytes());
+        assert!(ConfigClass::from_bytes(&rap).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::BufReader,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+use bevy::{asset::Asset, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ConfigClass,
+    p3d::{mlod_to_glb, P3dSettings},
+    paa::paa_to_ktx2,
+    pbo::Pbo,
+    rvmat::RvmatMaterial,
+};
+
+/// File name of the manifest in the output directory.
+pub const ASSET_MANIFEST: &str = "manifest.json";
+
+/// Converts Bohemia assets of directories and PBOs into formats Bevy loads natively, P3D to binary
+/// glTF, PAA to KTX2 and RVMAT to [`RvmatMaterial`], other files are copied as-is.
+///
+/// Files are written by their lowercase path including the PBO prefix, later inputs override
+/// earlier ones. Loading converted assets requires the `bevy_gltf` and `ktx2` features of Bevy.
+///
+/// The output is only meant for Bevy, models reference block-compressed KTX2 textures, which
+/// other glTF loaders don't support.
+pub struct AssetConverter {
+    output: PathBuf,
+    p3d: P3dSettings,
+    manifest: AssetManifest,
+    failures: Vec<(String, anyhow::Error)>,
+}
+
+impl AssetConverter {
+    pub fn new(output: impl Into<PathBuf>, p3d: P3dSettings) -> Self {
+        Self {
+            output: output.into(),
+            p3d,
+            manifest: AssetManifest::default(),
+            failures: Vec::new(),
+        }
+    }
+
+    /// Converts a PBO, or a directory including the PBOs in it.
+    pub fn convert(&mut self, input: &Path) -> Result<()> {
+        if input.is_dir() {
+            self.convert_directory(input, input)
+        } else {
+            self.convert_pbo(input)
+        }
+    }
+
+    pub fn convert_pbo(&mut self, path: &Path) -> Result<()> {
+        let mut input = BufReader::new(File::open(path)?);
+        let pbo = Pbo::read_from(&mut input)?;
+        let prefix = pbo.prefix();
+        for entry in &pbo.entries {
+            let bytes = pbo.read(&mut input, entry)?;
+            if prefix.is_empty() {
+                self.convert_file(&entry.name, &bytes)?;
+            } else {
+                self.convert_file(&format!("{prefix}\\{}", entry.name), &bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn convert_directory(&mut self, root: &Path, directory: &Path) -> Result<()> {
+        let mut paths = fs::read_dir(directory)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        paths.sort();
+        for path in paths {
+            if path.is_dir() {
+                self.convert_directory(root, &path)?;
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("pbo"))
+            {
+                // Unreadable PBOs are reported like files, and don't abort the batch
+                if let Err(error) = self.convert_pbo(&path) {
+                    self.failures.push((path.display().to_string(), error));
+                }
+            } else if let Ok(relative) = path.strip_prefix(root) {
+                self.convert_file(&relative.to_string_lossy(), &fs::read(&path)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts a file by its path relative to the root, files which fail to convert are copied
+    /// as-is and reported by [`Self::finish`].
+    pub fn convert_file(&mut self, path: &str, bytes: &[u8]) -> Result<()> {
+        let path = normalize_path(path);
+
+        // Files must stay inside of the output directory
+        if !Path::new(&path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            self.failures
+                .push((path, anyhow!("path leaves the output directory")));
+            return Ok(());
+        }
+
+        self.manifest.assets.remove(&path);
+        if let Some(converted) = converted_path(&path) {
+            match self.convert_bytes(&path, &converted, bytes) {
+                Ok(data) => {
+                    self.write(&converted, &data)?;
+                    self.manifest.assets.insert(path, converted);
+                    return Ok(());
+                }
+                Err(error) => self.failures.push((path.clone(), error)),
+            }
+        }
+        self.write(&path, bytes)
+    }
+
+    /// Writes the manifest, and returns it with the files and PBOs which failed to convert.
+    pub fn finish(self) -> Result<(AssetManifest, Vec<(String, anyhow::Error)>)> {
+        fs::create_dir_all(&self.output)?;
+        fs::write(
+            self.output.join(ASSET_MANIFEST),
+            serde_json::to_vec_pretty(&self.manifest)?,
+        )?;
+        Ok((self.manifest, self.failures))
+    }
+
+    fn convert_bytes(&self, path: &str, converted: &str, bytes: &[u8]) -> Result<Vec<u8>> {
+        match extension(path).as_str() {
+            "p3d" => {
+                // Textures are referenced relative to the model
+                let (glb, _) = mlod_to_glb(bytes, &self.p3d, |texture| {
+                    let texture = converted_path(texture).unwrap_or_else(|| texture.to_string());
+                    relative_uri(converted, &texture)
+                })?;
+                Ok(glb)
+            }
+            "paa" | "pac" => paa_to_ktx2(bytes),
+            "rvmat" => {
+                let mut material = RvmatMaterial::from_config(&ConfigClass::from_bytes(bytes)?);
+                for stage in &mut material.stages {
+                    if let Some(texture) = converted_path(&stage.texture) {
+                        stage.texture = texture;
+                    }
+                }
+                Ok(serde_json::to_vec_pretty(&material)?)
+            }
+            _ => bail!("unsupported file type"),
+        }
+    }
+
+    fn write(&self, path: &str, bytes: &[u8]) -> Result<()> {
+        let output = self.output.join(path);
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(output, bytes)?;
+        Ok(())
+    }
+}
+
+/// Mapping of original to converted paths, written by [`AssetConverter`].
+///
+/// Release builds can insert it as resource, and load assets by their original path with
+/// [`AssetManifest::load`].
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AssetManifest {
+    /// Converted paths by lowercase original path, both relative to the root.
+    pub assets: BTreeMap<String, String>,
+}
+
+impl AssetManifest {
+    pub fn read(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Returns the converted path of an asset path with optional label, or the normalized path if
+    /// it was not converted. The `Scene` of a model becomes the first glTF scene, `Scene0`.
+    pub fn resolve(&self, path: &str) -> String {
+        let (path, label) = match path.split_once('#') {
+            Some((path, label)) => (path, Some(label)),
+            None => (path, None),
+        };
+        let path = normalize_path(path);
+        let (path, label) = match self.assets.get(&path) {
+            Some(converted) if converted.ends_with(".glb") && label == Some("Scene") => {
+                (converted.clone(), Some("Scene0"))
+            }
+            Some(converted) => (converted.clone(), label),
+            None => (path, label),
+        };
+        match label {
+            Some(label) => format!("{path}#{label}"),
+            None => path,
+        }
+    }
+
+    pub fn load<T: Asset>(&self, asset_server: &AssetServer, path: &str) -> Handle<T> {
+        asset_server.load(self.resolve(path).as_str())
+    }
+}
+
+/// Returns the path of the converted file, if the file type is converted.
+pub fn converted_path(path: &str) -> Option<String> {
+    let (stem, _) = path.rsplit_once('.')?;
+    let extension = match extension(path).as_str() {
+        "p3d" => "glb",
+        "paa" | "pac" => "ktx2",
+        "rvmat" => "rvmat.json",
+        _ => return None,
+    };
+    Some(format!("{stem}.{extension}"))
+}
+
+fn extension(path: &str) -> String {
+    path.rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+fn normalize_path(path: &str) -> String {
+    path.trim_start_matches(['\\', '/'])
+        .replace('\\', "/")
+        .to_ascii_lowercase()
+}
+
+/// Returns the uri of a file relative to the directory of another file, both relative to the root.
+fn relative_uri(from: &str, to: &str) -> String {
+    let from: Vec<_> = from.split('/').collect();
+    let from = &from[..from.len() - 1];
+    let to: Vec<_> = to.split('/').collect();
+    let common = from
+        .iter()
+        .zip(&to[..to.len() - 1])
+        .take_while(|(from, to)| from == to)
+        .count();
+    "../".repeat(from.len() - common) + &to[common..].join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converted_paths() {
+        assert_eq!(converted_path("a/b.p3d").as_deref(), Some("a/b.glb"));
+        assert_eq!(converted_path("a/b_co.PAA").as_deref(), Some("a/b_co.ktx2"));
+        assert_eq!(
+            converted_path("a/b.rvmat").as_deref(),
+            Some("a/b.rvmat.json")
+        );
+        assert_eq!(converted_path("a/config.cpp"), None);
+        assert_eq!(converted_path("a/b"), None);
+    }
+
+    #[test]
+    fn relative_uris() {
+        assert_eq!(relative_uri("a/b/m.glb", "a/b/t.ktx2"), "t.ktx2");
+        assert_eq!(relative_uri("a/b/m.glb", "a/c/t.ktx2"), "../c/t.ktx2");
+        assert_eq!(relative_uri("m.glb", "a/t.ktx2"), "a/t.ktx2");
+        assert_eq!(relative_uri("a/m.glb", "t.ktx2"), "../t.ktx2");
+        assert_eq!(relative_uri("a/a/m.glb", "a/t.ktx2"), "../t.ktx2");
+    }
+
+    #[test]
+    fn resolve() {
+        let mut manifest = AssetManifest::default();
+        manifest
+            .assets
+            .insert("a/m.p3d".to_string(), "a/m.glb".to_string());
+        manifest
+            .assets
+            .insert("a/t_co.paa".to_string(), "a/t_co.ktx2".to_string());
+        assert_eq!(manifest.resolve("\\A\\M.p3d#Scene"), "a/m.glb#Scene0");
+        assert_eq!(manifest.resolve("a/t_co.paa"), "a/t_co.ktx2");
+        assert_eq!(manifest.resolve("a/config.cpp"), "a/config.cpp");
+    }
+
+    #[test]
+    fn corrupt_pbo() {
+        let root = std::env::temp_dir().join(format!("vixen_convert_{}", std::process::id()));
+        let input = root.join("input");
+        fs::create_dir_all(input.join("addons")).unwrap();
+        fs::write(input.join("addons/broken.pbo"), b"not a pbo").unwrap();
+        fs::write(input.join("readme.txt"), b"vixen").unwrap();
+
+        // Files after the PBO are still converted
+        let mut converter = AssetConverter::new(root.join("output"), P3dSettings::default());
+        converter.convert(&input).unwrap();
+        let (_, failures) = converter.finish().unwrap();
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].0.ends_with("broken.pbo"));
+        assert_eq!(fs::read(root.join("output/readme.txt")).unwrap(), b"vixen");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,31 @@
+use bevy::reflect::TypeUuid;
+
+use crate::p3d::P3dFaceFlags;
+
+/// Source faces of a LOD, by their index in the P3D including invalid ones, labeled `<lod>/Faces`.
+#[derive(TypeUuid, Clone, Debug, Default)]
+#[uuid = "7a1c5e93-d2f4-4b86-9e07-3f8b2a6d1c54"]
+pub struct P3dFaces(pub Vec<P3dFace>);
+
+#[derive(Clone, Debug)]
+pub struct P3dFace {
+    pub flags: P3dFaceFlags,
+    pub texture: String,
+    /// RVMAT, which references the surface type.
+    pub material: String,
+    /// Named selections containing the face.
+    pub selections: Vec<String>,
+}
+
+/// Index of the source face of each triangle of a mesh, in index buffer order, labeled
+/// `<lod>/Triangles` and `<lod>/Batch<batch>/Triangles`.
+#[derive(TypeUuid, Clone, Debug, Default)]
+#[uuid = "e58d3b07-6a2c-4f19-b4e1-9c7f0d2a8e36"]
+pub struct P3dTriangleFaces(pub Vec<u32>);
+
+impl P3dTriangleFaces {
+    /// Returns the source face of a triangle, e.g. of a picking hit.
+    pub fn face<'a>(&self, faces: &'a P3dFaces, triangle: usize) -> Option<&'a P3dFace> {
+        faces.0.get(*self.0.get(triangle)? as usize)
+    }
+}
@@ -0,0 +1,213 @@
+use std::io::{Cursor, Read};
+
+use anyhow::Result;
+use bevy::{
+    asset::{AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::HashMap,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::paa::paa_size;
+
+/// Upper bound of pages, as the page count is derived from the glyphs.
+const MAXIMUM_PAGE_COUNT: usize = 64;
+
+/// Registers the FXY loader and the font asset type.
+#[derive(Default)]
+pub struct FxyPlugin;
+
+impl Plugin for FxyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<FxyFont>().init_asset_loader::<FxyLoader>();
+    }
+}
+
+/// Bohemia bitmap font loader, glyphs are stored on PAA pages next to the FXY, which are named
+/// after it with a two-digit page number, e.g. `tahomab24-01.paa`.
+#[derive(Default)]
+pub struct FxyLoader;
+
+impl AssetLoader for FxyLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move { load_fxy(bytes, load_context).await })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["fxy"]
+    }
+}
+
+/// Bitmap font, with a texture atlas per page.
+#[derive(TypeUuid, Clone, Debug, Default)]
+#[uuid = "9a2e5c71-3f04-4d8b-b6e9-5c1a7d3f2e80"]
+pub struct FxyFont {
+    pub pages: Vec<Handle<TextureAtlas>>,
+    pub glyphs: HashMap<char, FxyGlyph>,
+    /// Height of the tallest glyph, used as line height.
+    pub line_height: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FxyGlyph {
+    pub page: usize,
+    /// Index of the glyph in the page's texture atlas.
+    pub index: usize,
+    /// Size in pixels, which is also the advance.
+    pub size: Vec2,
+}
+
+/// Glyph placed by [`FxyFont::layout`].
+#[derive(Clone, Copy, Debug)]
+pub struct FxyPositionedGlyph {
+    pub glyph: FxyGlyph,
+    /// Top-left corner, y pointing down.
+    pub position: Vec2,
+}
+
+impl FxyFont {
+    /// Lays out text in pixels, characters without a glyph are skipped.
+    pub fn layout(&self, text: &str, scale: f32) -> Vec<FxyPositionedGlyph> {
+        let mut glyphs = Vec::new();
+        let mut position = Vec2::ZERO;
+        for character in text.chars() {
+            if character == '\n' {
+                position = Vec2::new(0.0, position.y + self.line_height * scale);
+                continue;
+            }
+
+            let Some(&glyph) = self.glyphs.get(&character) else {
+                continue;
+            };
+            glyphs.push(FxyPositionedGlyph { glyph, position });
+            position.x += glyph.size.x * scale;
+        }
+
+        glyphs
+    }
+
+    /// Returns the size of text in pixels.
+    pub fn measure(&self, text: &str, scale: f32) -> Vec2 {
+        let width = text
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .filter_map(|character| self.glyphs.get(&character))
+                    .map(|glyph| glyph.size.x)
+                    .sum::<f32>()
+            })
+            .fold(0.0, f32::max);
+        let lines = text.split('\n').count();
+        Vec2::new(width, lines as f32 * self.line_height) * scale
+    }
+}
+
+async fn load_fxy<'a, 'b>(bytes: &'a [u8], load_context: &'a mut LoadContext<'b>) -> Result<()> {
+    let entries = FxyEntry::read_all(&mut Cursor::new(bytes))?;
+
+    let path = load_context.path().to_path_buf();
+    let stem = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let page_count = entries
+        .iter()
+        .map(|entry| entry.page as usize + 1)
+        .max()
+        .unwrap_or_default()
+        .min(MAXIMUM_PAGE_COUNT);
+
+    // Pages are required up front, as atlases need their size
+    let mut atlases = Vec::new();
+    let mut dependencies = Vec::new();
+    for page in 0..page_count {
+        let page_path = path.with_file_name(format!("{}-{:02}.paa", stem, page + 1));
+        // Pages are consecutive, glyphs on pages after a missing one are skipped
+        let size = match load_context.read_asset_bytes(&page_path).await {
+            Ok(page_bytes) => paa_size(&page_bytes).unwrap_or_default(),
+            Err(error) => {
+                warn!(
+                    "Failed to read font page {}: {}",
+                    page_path.display(),
+                    error
+                );
+                break;
+            }
+        };
+        let page_path = AssetPath::new(page_path, None);
+        atlases.push(TextureAtlas::new_empty(
+            load_context.get_handle(page_path.clone()),
+            size.as_vec2(),
+        ));
+        dependencies.push(page_path);
+    }
+
+    let mut font = FxyFont::default();
+    for entry in &entries {
+        let Some(character) = char::from_u32(entry.character as u32) else {
+            continue;
+        };
+        let min = Vec2::new(entry.x as f32, entry.y as f32);
+        let size = Vec2::new(entry.width as f32, entry.height as f32);
+        let page = entry.page as usize;
+        let Some(atlas) = atlases.get_mut(page) else {
+            continue;
+        };
+        font.glyphs.insert(
+            character,
+            FxyGlyph {
+                page,
+                index: atlas.add_texture(Rect {
+                    min,
+                    max: min + size,
+                }),
+                size,
+            },
+        );
+        font.line_height = font.line_height.max(size.y);
+    }
+
+    for (page, (atlas, dependency)) in atlases.into_iter().zip(dependencies).enumerate() {
+        font.pages.push(load_context.set_labeled_asset(
+            format!("Page{page}").as_str(),
+            LoadedAsset::new(atlas).with_dependency(dependency),
+        ));
+    }
+    load_context.set_default_asset(LoadedAsset::new(font));
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct FxyEntry {
+    character: u16,
+    page: u16,
+    x: u16,
+    y: u16,
+    width: u16,
+    height: u16,
+}
+
+impl FxyEntry {
+    fn read_all<R: Read>(input: &mut R) -> Result<Vec<Self>> {
+        let mut entries = Vec::new();
+        while let Ok(character) = input.read_u16::<LittleEndian>() {
+            entries.push(Self {
+                character,
+                page: input.read_u16::<LittleEndian>()?,
+                x: input.read_u16::<LittleEndian>()?,
+                y: input.read_u16::<LittleEndian>()?,
+                width: input.read_u16::<LittleEndian>()?,
+                height: input.read_u16::<LittleEndian>()?,
+            });
+        }
+
+        Ok(entries)
+    }
+}
@@ -0,0 +1,263 @@
+use std::io::Write;
+
+use anyhow::Result;
+use bevy::{prelude::*, utils::HashMap};
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde_json::{json, Value};
+
+use crate::paa::next_multiple_of;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+/// Writer of binary glTF with a single embedded buffer, images are referenced by uri.
+///
+/// Images are KTX2 without Basis Universal supercompression, which is not part of glTF, but loaded
+/// by Bevy by their `image/ktx2` mime type.
+#[derive(Default)]
+pub(crate) struct GlbWriter {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+    images: Vec<Value>,
+    image_indices: HashMap<String, usize>,
+    materials: Vec<Value>,
+    meshes: Vec<Value>,
+    unlit: bool,
+}
+
+/// Triangle list with a material index.
+pub(crate) struct GlbPrimitive<'a> {
+    pub positions: &'a [[f32; 3]],
+    pub normals: &'a [[f32; 3]],
+    pub uvs: &'a [[f32; 2]],
+    pub indices: &'a [u32],
+    pub material: usize,
+}
+
+impl GlbWriter {
+    /// Adds a material, colors are converted to linear, the base color texture is an image uri.
+    pub(crate) fn add_material(
+        &mut self,
+        material: &StandardMaterial,
+        base_color_texture: Option<&str>,
+    ) -> usize {
+        let mut pbr = json!({
+            "baseColorFactor": material.base_color.as_linear_rgba_f32(),
+            "metallicFactor": material.metallic,
+            "roughnessFactor": material.perceptual_roughness,
+        });
+        if let Some(uri) = base_color_texture {
+            let image_count = self.images.len();
+            let image = *self
+                .image_indices
+                .entry(uri.to_string())
+                .or_insert(image_count);
+            if image == image_count {
+                self.images
+                    .push(json!({ "uri": uri, "mimeType": "image/ktx2" }));
+            }
+            pbr["baseColorTexture"] = json!({ "index": image });
+        }
+
+        let mut value = json!({
+            "pbrMetallicRoughness": pbr,
+            "doubleSided": material.double_sided,
+        });
+        let emissive = material.emissive.as_linear_rgba_f32();
+        if emissive[..3].iter().any(|&value| value != 0.0) {
+            value["emissiveFactor"] = json!(emissive[..3]);
+        }
+        match material.alpha_mode {
+            AlphaMode::Opaque => {}
+            AlphaMode::Mask(cutoff) => {
+                value["alphaMode"] = json!("MASK");
+                value["alphaCutoff"] = json!(cutoff);
+            }
+            AlphaMode::Blend => value["alphaMode"] = json!("BLEND"),
+        }
+        if material.unlit {
+            value["extensions"] = json!({ "KHR_materials_unlit": {} });
+            self.unlit = true;
+        }
+
+        self.materials.push(value);
+        self.materials.len() - 1
+    }
+
+    /// Adds a mesh, primitives without triangles are skipped, returns `None` if none is left.
+    pub(crate) fn add_mesh(&mut self, name: &str, primitives: &[GlbPrimitive]) -> Option<usize> {
+        let primitives: Vec<_> = primitives
+            .iter()
+            .filter(|primitive| !primitive.indices.is_empty())
+            .map(|primitive| {
+                json!({
+                    "attributes": {
+                        "POSITION": self.add_positions(primitive.positions),
+                        "NORMAL": self.add_floats(primitive.normals, "VEC3"),
+                        "TEXCOORD_0": self.add_floats(primitive.uvs, "VEC2"),
+                    },
+                    "indices": self.add_indices(primitive.indices, primitive.positions.len()),
+                    "material": primitive.material,
+                })
+            })
+            .collect();
+        if primitives.is_empty() {
+            return None;
+        }
+
+        self.meshes.push(json!({
+            "name": name,
+            "primitives": primitives,
+        }));
+        Some(self.meshes.len() - 1)
+    }
+
+    /// Writes the file, with a node of the given mesh in the scene, other meshes are only referenced
+    /// by index. Without a scene mesh the file has no scene.
+    pub(crate) fn finish(self, scene_mesh: Option<usize>) -> Result<Vec<u8>> {
+        let nodes: Vec<_> = scene_mesh
+            .into_iter()
+            .map(|mesh| json!({ "name": self.meshes[mesh]["name"], "mesh": mesh }))
+            .collect();
+        let textures: Vec<_> = (0..self.images.len())
+            .map(|image| json!({ "source": image }))
+            .collect();
+        let mut root = json!({
+            "asset": { "version": "2.0", "generator": env!("CARGO_PKG_NAME") },
+            "nodes": nodes,
+            "meshes": self.meshes,
+            "materials": self.materials,
+            "textures": textures,
+            "images": self.images,
+            "accessors": self.accessors,
+            "bufferViews": self.buffer_views,
+            "buffers": [{ "byteLength": self.buffer.len() }],
+        });
+        if scene_mesh.is_some() {
+            root["scene"] = json!(0);
+            root["scenes"] = json!([{ "nodes": [0] }]);
+        }
+        if self.unlit {
+            root["extensionsUsed"] = json!(["KHR_materials_unlit"]);
+        }
+
+        // Arrays and buffers must not be empty
+        if self.buffer.is_empty() {
+            root["buffers"] = json!([]);
+        }
+        if let Some(root) = root.as_object_mut() {
+            root.retain(|_, value| !value.as_array().is_some_and(Vec::is_empty));
+        }
+
+        // Chunks are padded to four bytes, the JSON chunk with spaces
+        let mut json = serde_json::to_vec(&root)?;
+        json.resize(next_multiple_of(json.len(), 4), b' ');
+        let mut buffer = self.buffer;
+        buffer.resize(next_multiple_of(buffer.len(), 4), 0);
+
+        let mut length = 12 + 8 + json.len();
+        if !buffer.is_empty() {
+            length += 8 + buffer.len();
+        }
+        let mut output = Vec::with_capacity(length);
+        output.write_all(b"glTF")?;
+        output.write_u32::<LittleEndian>(2)?;
+        output.write_u32::<LittleEndian>(length as u32)?;
+        output.write_u32::<LittleEndian>(json.len() as u32)?;
+        output.write_all(b"JSON")?;
+        output.write_all(&json)?;
+        if !buffer.is_empty() {
+            output.write_u32::<LittleEndian>(buffer.len() as u32)?;
+            output.write_all(b"BIN\0")?;
+            output.write_all(&buffer)?;
+        }
+
+        Ok(output)
+    }
+
+    fn add_positions(&mut self, positions: &[[f32; 3]]) -> usize {
+        // Positions require bounds
+        let (min, max) =
+            positions
+                .iter()
+                .fold(([f32::MAX; 3], [f32::MIN; 3]), |(min, max), position| {
+                    (
+                        [0, 1, 2].map(|i| min[i].min(position[i])),
+                        [0, 1, 2].map(|i| max[i].max(position[i])),
+                    )
+                });
+        let accessor = self.add_floats(positions, "VEC3");
+        self.accessors[accessor]["min"] = json!(min);
+        self.accessors[accessor]["max"] = json!(max);
+        accessor
+    }
+
+    fn add_floats<const N: usize>(&mut self, values: &[[f32; N]], kind: &str) -> usize {
+        let data: Vec<_> = values
+            .iter()
+            .flatten()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        self.add_accessor(&data, ARRAY_BUFFER, FLOAT, values.len(), kind)
+    }
+
+    fn add_indices(&mut self, indices: &[u32], vertex_count: usize) -> usize {
+        // Use 16-bit indices if all vertices are addressable
+        if vertex_count <= u16::MAX as usize + 1 {
+            let data: Vec<_> = indices
+                .iter()
+                .flat_map(|&index| (index as u16).to_le_bytes())
+                .collect();
+            self.add_accessor(
+                &data,
+                ELEMENT_ARRAY_BUFFER,
+                UNSIGNED_SHORT,
+                indices.len(),
+                "SCALAR",
+            )
+        } else {
+            let data: Vec<_> = indices
+                .iter()
+                .flat_map(|index| index.to_le_bytes())
+                .collect();
+            self.add_accessor(
+                &data,
+                ELEMENT_ARRAY_BUFFER,
+                UNSIGNED_INT,
+                indices.len(),
+                "SCALAR",
+            )
+        }
+    }
+
+    fn add_accessor(
+        &mut self,
+        data: &[u8],
+        target: u32,
+        component_type: u32,
+        count: usize,
+        kind: &str,
+    ) -> usize {
+        // Accessors have to be aligned to their component size
+        self.buffer
+            .resize(next_multiple_of(self.buffer.len(), 4), 0);
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": data.len(),
+            "target": target,
+        }));
+        self.buffer.extend_from_slice(data);
+        self.accessors.push(json!({
+            "bufferView": self.buffer_views.len() - 1,
+            "componentType": component_type,
+            "count": count,
+            "type": kind,
+        }));
+        self.accessors.len() - 1
+    }
+}
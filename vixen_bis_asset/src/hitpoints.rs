@@ -0,0 +1,58 @@
+use bevy::{prelude::*, reflect::TypeUuid};
+
+/// Hit zones of a model, from the named selections of the hitpoints LOD.
+///
+/// Every sphere starts out with `P3dSettings::hit_point_radius`. The actual radius of a zone is
+/// the `radius` of its `HitPoints` class in the config.cpp of the vehicle using the model, which
+/// the loader can't know, so apply it with [`HitZones::set_radius`].
+#[derive(TypeUuid, Clone, Debug, Default)]
+#[uuid = "0b3d9f6e-2c71-4e8a-a5d4-7f19c2e8b043"]
+pub struct HitZones(pub Vec<HitZone>);
+
+#[derive(Clone, Debug)]
+pub struct HitZone {
+    pub name: String,
+    pub spheres: Vec<HitSphere>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct HitSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl HitZones {
+    /// Returns the names of all zones containing the model space position.
+    pub fn zones_at(&self, position: Vec3) -> impl Iterator<Item = &str> {
+        self.0
+            .iter()
+            .filter(move |zone| {
+                zone.spheres.iter().any(|sphere| {
+                    sphere.center.distance_squared(position) <= sphere.radius * sphere.radius
+                })
+            })
+            .map(|zone| zone.name.as_str())
+    }
+
+    /// Returns the names of all zones containing the world space position, given the global
+    /// transform of the model's scene root.
+    pub fn zones_at_world(
+        &self,
+        transform: &GlobalTransform,
+        position: Vec3,
+    ) -> impl Iterator<Item = &str> {
+        self.zones_at(transform.affine().inverse().transform_point3(position))
+    }
+
+    /// Overrides the radius of all spheres of a zone, radii are defined by the `HitPoints`
+    /// classes in config.cpp.
+    pub fn set_radius(&mut self, name: &str, radius: f32) {
+        for zone in &mut self.0 {
+            if zone.name.eq_ignore_ascii_case(name) {
+                for sphere in &mut zone.spheres {
+                    sphere.radius = radius;
+                }
+            }
+        }
+    }
+}
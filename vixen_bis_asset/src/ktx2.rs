@@ -0,0 +1,155 @@
+use std::io::Write;
+
+use anyhow::{bail, Result};
+use bevy::{prelude::*, render::render_resource::TextureFormat};
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::paa::next_multiple_of;
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// Length of the header and the index, which are followed by the level index.
+const HEADER_SIZE: usize = 80;
+
+/// Data format descriptor channels.
+const CHANNEL_RED: u8 = 0;
+const CHANNEL_GREEN: u8 = 1;
+const CHANNEL_BLUE: u8 = 2;
+const CHANNEL_ALPHA: u8 = 15;
+const CHANNEL_BC1A_ALPHA_PRESENT: u8 = 1;
+
+/// Vulkan format, color model, and channel, bit offset and bit length of each sample.
+struct Ktx2Format {
+    vk_format: u32,
+    color_model: u8,
+    samples: &'static [(u8, u16, u8)],
+}
+
+impl Ktx2Format {
+    fn from_texture_format(format: TextureFormat) -> Option<Self> {
+        Some(match format {
+            TextureFormat::Bc1RgbaUnorm => Self {
+                vk_format: 133,
+                color_model: 128,
+                samples: &[(CHANNEL_BC1A_ALPHA_PRESENT, 0, 64)],
+            },
+            TextureFormat::Bc2RgbaUnorm => Self {
+                vk_format: 135,
+                color_model: 129,
+                samples: &[(CHANNEL_ALPHA, 0, 64), (CHANNEL_RED, 64, 64)],
+            },
+            TextureFormat::Bc3RgbaUnorm => Self {
+                vk_format: 137,
+                color_model: 130,
+                samples: &[(CHANNEL_ALPHA, 0, 64), (CHANNEL_RED, 64, 64)],
+            },
+            TextureFormat::Rgba8Unorm => Self {
+                vk_format: 37,
+                color_model: 1,
+                samples: &[
+                    (CHANNEL_RED, 0, 8),
+                    (CHANNEL_GREEN, 8, 8),
+                    (CHANNEL_BLUE, 16, 8),
+                    (CHANNEL_ALPHA, 24, 8),
+                ],
+            },
+            _ => return None,
+        })
+    }
+}
+
+/// Writes a 2D image with all of its mipmaps as KTX2 without supercompression, supports the
+/// formats produced by the PAA loader.
+pub(crate) fn write_ktx2(image: &Image) -> Result<Vec<u8>> {
+    let descriptor = &image.texture_descriptor;
+    let Some(format) = Ktx2Format::from_texture_format(descriptor.format) else {
+        bail!("unsupported texture format {:?}", descriptor.format)
+    };
+    let info = descriptor.format.describe();
+    let (block_width, block_height) = info.block_dimensions;
+    let block_size = info.block_size as usize;
+
+    // Split the data, which starts with the largest mipmap, into levels
+    let (width, height) = (descriptor.size.width, descriptor.size.height);
+    let level_count = descriptor.mip_level_count as usize;
+    let mut levels = Vec::with_capacity(level_count);
+    let mut offset = 0;
+    for level in 0..level_count {
+        let level_width = (width >> level).max(1) as usize;
+        let level_height = (height >> level).max(1) as usize;
+        let size = next_multiple_of(level_width, block_width as usize) / block_width as usize
+            * (next_multiple_of(level_height, block_height as usize) / block_height as usize)
+            * block_size;
+        let Some(data) = image.data.get(offset..offset + size) else {
+            bail!("image data ends in mipmap {level}")
+        };
+        levels.push(data);
+        offset += size;
+    }
+
+    let dfd_offset = HEADER_SIZE + level_count * 24;
+    let dfd_length = 4 + 24 + format.samples.len() * 16;
+
+    // Levels are stored from the smallest to the largest, aligned to the block size and four bytes,
+    // both are powers of two
+    let alignment = block_size.max(4);
+    let mut level_offsets = vec![0; level_count];
+    let mut end = dfd_offset + dfd_length;
+    for (level, data) in levels.iter().enumerate().rev() {
+        level_offsets[level] = next_multiple_of(end, alignment);
+        end = level_offsets[level] + data.len();
+    }
+
+    let mut output = Vec::with_capacity(end);
+    output.write_all(&IDENTIFIER)?;
+    output.write_u32::<LittleEndian>(format.vk_format)?;
+    // Type size is one for block-compressed and 8-bit formats
+    output.write_u32::<LittleEndian>(1)?;
+    output.write_u32::<LittleEndian>(width)?;
+    output.write_u32::<LittleEndian>(height)?;
+    // Depth, layers, faces, levels and supercompression
+    output.write_u32::<LittleEndian>(0)?;
+    output.write_u32::<LittleEndian>(0)?;
+    output.write_u32::<LittleEndian>(1)?;
+    output.write_u32::<LittleEndian>(level_count as u32)?;
+    output.write_u32::<LittleEndian>(0)?;
+    // Data format descriptor, without key/value and supercompression global data
+    output.write_u32::<LittleEndian>(dfd_offset as u32)?;
+    output.write_u32::<LittleEndian>(dfd_length as u32)?;
+    output.write_u32::<LittleEndian>(0)?;
+    output.write_u32::<LittleEndian>(0)?;
+    output.write_u64::<LittleEndian>(0)?;
+    output.write_u64::<LittleEndian>(0)?;
+    for (&offset, data) in level_offsets.iter().zip(&levels) {
+        output.write_u64::<LittleEndian>(offset as u64)?;
+        output.write_u64::<LittleEndian>(data.len() as u64)?;
+        output.write_u64::<LittleEndian>(data.len() as u64)?;
+    }
+
+    // Basic data format descriptor block, with BT.709 primaries and linear transfer
+    output.write_u32::<LittleEndian>(dfd_length as u32)?;
+    output.write_u32::<LittleEndian>(0)?;
+    output.write_u16::<LittleEndian>(2)?;
+    output.write_u16::<LittleEndian>(dfd_length as u16 - 4)?;
+    output.write_all(&[format.color_model, 1, 1, 0])?;
+    output.write_all(&[block_width - 1, block_height - 1, 0, 0])?;
+    output.write_all(&[block_size as u8, 0, 0, 0, 0, 0, 0, 0])?;
+    for &(channel, bit_offset, bit_length) in format.samples {
+        output.write_u16::<LittleEndian>(bit_offset)?;
+        output.write_u8(bit_length - 1)?;
+        output.write_u8(channel)?;
+        output.write_u32::<LittleEndian>(0)?;
+        output.write_u32::<LittleEndian>(0)?;
+        // Upper bound of the sample, all bits set for compressed formats
+        output.write_u32::<LittleEndian>(if bit_length == 8 { 255 } else { u32::MAX })?;
+    }
+
+    for (level, data) in levels.iter().enumerate().rev() {
+        output.resize(level_offsets[level], 0);
+        output.write_all(data)?;
+    }
+
+    Ok(output)
+}
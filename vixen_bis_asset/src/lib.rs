@@ -1,5 +1,46 @@
+pub use animation::*;
+pub use compression::*;
+pub use config::*;
+pub use convert::*;
+pub use faces::*;
+pub use fxy::*;
+pub use hitpoints::*;
+pub use lip::*;
+pub use navigation::*;
 pub use p3d::*;
 pub use paa::*;
+pub use pbo::*;
+pub use plugin::*;
+pub use rvmat::*;
+pub use signature::*;
+pub use skeleton::*;
+pub use source::*;
+pub use sqm::*;
+pub use terrain::*;
+pub use texheaders::*;
+pub use wss::*;
 
+mod animation;
+mod compression;
+mod config;
+mod convert;
+mod faces;
+mod fxy;
+mod gltf;
+mod hitpoints;
+mod ktx2;
+mod lip;
+mod mesh;
+mod navigation;
 mod p3d;
 mod paa;
+mod pbo;
+mod plugin;
+mod rvmat;
+mod signature;
+mod skeleton;
+mod source;
+mod sqm;
+mod terrain;
+mod texheaders;
+mod wss;
@@ -0,0 +1,212 @@
+use anyhow::{bail, Result};
+use bevy::{
+    animation::{AnimationClip, EntityPath, Keyframes, VariableCurve},
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use thiserror::Error;
+
+/// Largest intensity, fully open mouth.
+const MAXIMUM_INTENSITY: u8 = 7;
+
+/// Registers the LIP loader and the lip-sync asset type.
+#[derive(Default)]
+pub struct LipPlugin;
+
+impl Plugin for LipPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<LipSync>().init_asset_loader::<LipLoader>();
+    }
+}
+
+#[derive(Default)]
+pub struct LipLoader;
+
+impl AssetLoader for LipLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move { load_lip(bytes, load_context).await })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["lip"]
+    }
+}
+
+#[derive(Error, Debug)]
+enum LipError {
+    #[error("invalid line {0}")]
+    InvalidLine(usize),
+}
+
+async fn load_lip<'a, 'b>(bytes: &'a [u8], load_context: &'a mut LoadContext<'b>) -> Result<()> {
+    load_context.set_default_asset(LoadedAsset::new(LipSync::parse(&String::from_utf8_lossy(
+        bytes,
+    ))?));
+
+    Ok(())
+}
+
+/// Mouth movement of a voice line, as frames of time and intensity.
+#[derive(TypeUuid, Clone, Debug, Default)]
+#[uuid = "2d8f4b16-7e3a-4c59-a0b2-6f1e9d5c8a43"]
+pub struct LipSync {
+    /// Duration of a frame in seconds.
+    pub frame: f32,
+    pub frames: Vec<LipFrame>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LipFrame {
+    /// Start in seconds.
+    pub time: f32,
+    /// Mouth opening, from 0 to 7.
+    pub intensity: u8,
+}
+
+impl LipSync {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lip_sync = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("version") {
+                continue;
+            }
+
+            if let Some(frame) = line.strip_prefix("frame") {
+                let Some(frame) = frame
+                    .trim_start()
+                    .strip_prefix('=')
+                    .and_then(|frame| frame.trim().parse().ok())
+                else {
+                    bail!(LipError::InvalidLine(index + 1))
+                };
+                lip_sync.frame = frame;
+                continue;
+            }
+
+            let Some((time, intensity)) = line.split_once(',').and_then(|(time, intensity)| {
+                Some((time.trim().parse().ok()?, intensity.trim().parse().ok()?))
+            }) else {
+                bail!(LipError::InvalidLine(index + 1))
+            };
+            lip_sync.frames.push(LipFrame {
+                time,
+                intensity: MAXIMUM_INTENSITY.min(intensity),
+            });
+        }
+        lip_sync.frames.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Ok(lip_sync)
+    }
+
+    /// Returns the mouth opening at a time in seconds, from 0 to 1.
+    pub fn sample(&self, time: f32) -> f32 {
+        let frame = self.frames.partition_point(|frame| frame.time <= time);
+        frame
+            .checked_sub(1)
+            .map_or(0.0, |frame| self.frames[frame].intensity as f32)
+            / MAXIMUM_INTENSITY as f32
+    }
+
+    /// Duration in seconds, until the end of the last frame.
+    pub fn duration(&self) -> f32 {
+        self.frames
+            .last()
+            .map_or(0.0, |frame| frame.time + self.frame)
+    }
+
+    /// Creates a clip rotating a jaw bone between its rest and open rotation, as Bevy has no
+    /// morph target animation.
+    pub fn animation_clip(&self, jaw: EntityPath, open: Quat) -> AnimationClip {
+        let mut clip = AnimationClip::default();
+        clip.add_curve_to_path(
+            jaw,
+            VariableCurve {
+                keyframe_timestamps: self.frames.iter().map(|frame| frame.time).collect(),
+                keyframes: Keyframes::Rotation(
+                    self.frames
+                        .iter()
+                        .map(|frame| {
+                            Quat::IDENTITY
+                                .slerp(open, frame.intensity as f32 / MAXIMUM_INTENSITY as f32)
+                        })
+                        .collect(),
+                ),
+            },
+        );
+        clip
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIP: &str = "version 1.01\r\nframe = 0.04\r\n\r\n0.000, 0\r\n0.120, 9\r\n0.040, 3\r\n";
+
+    #[test]
+    fn parse() {
+        let lip_sync = LipSync::parse(LIP).unwrap();
+        assert_eq!(lip_sync.frame, 0.04);
+
+        // Frames are sorted by time, intensities clamped
+        let frames: Vec<_> = lip_sync
+            .frames
+            .iter()
+            .map(|frame| (frame.time, frame.intensity))
+            .collect();
+        assert_eq!(frames, [(0.0, 0), (0.04, 3), (0.12, 7)]);
+
+        assert!(matches!(
+            LipSync::parse("frame = 0.04\n0.0 3")
+                .unwrap_err()
+                .downcast_ref(),
+            Some(LipError::InvalidLine(2))
+        ));
+        assert!(matches!(
+            LipSync::parse("frame = fast").unwrap_err().downcast_ref(),
+            Some(LipError::InvalidLine(1))
+        ));
+    }
+
+    #[test]
+    fn sample() {
+        let lip_sync = LipSync::parse(LIP).unwrap();
+
+        // Frames hold their intensity until the next one
+        assert_eq!(lip_sync.sample(-1.0), 0.0);
+        assert_eq!(lip_sync.sample(0.0), 0.0);
+        assert_eq!(lip_sync.sample(0.04), 3.0 / 7.0);
+        assert_eq!(lip_sync.sample(0.08), 3.0 / 7.0);
+        assert_eq!(lip_sync.sample(0.12), 1.0);
+        assert_eq!(lip_sync.sample(0.15), 1.0);
+
+        assert!((lip_sync.duration() - 0.16).abs() < 1e-6);
+        assert_eq!(LipSync::default().duration(), 0.0);
+        assert_eq!(LipSync::default().sample(0.0), 0.0);
+    }
+
+    #[test]
+    fn animation_clip() {
+        let lip_sync = LipSync::parse(LIP).unwrap();
+        let jaw = EntityPath {
+            parts: vec![Name::new("jaw")],
+        };
+        let open = Quat::from_rotation_x(0.5);
+        let clip = lip_sync.animation_clip(jaw.clone(), open);
+
+        let curve = &clip.curves()[&jaw][0];
+        assert_eq!(curve.keyframe_timestamps, [0.0, 0.04, 0.12]);
+        let Keyframes::Rotation(rotations) = &curve.keyframes else {
+            panic!("expected rotation keyframes")
+        };
+        assert_eq!(rotations[0], Quat::IDENTITY);
+        assert!(rotations[1].abs_diff_eq(Quat::IDENTITY.slerp(open, 3.0 / 7.0), 1e-6));
+        assert!(rotations[2].abs_diff_eq(open, 1e-6));
+    }
+}
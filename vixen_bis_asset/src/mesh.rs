@@ -0,0 +1,215 @@
+const CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// Reorders the triangles of an indexed triangle list for post-transform vertex cache efficiency,
+/// returns the previous index of each triangle.
+///
+/// See https://tomforsyth1000.github.io/papers/fast_vert_cache_opt.html
+pub(crate) fn optimize_vertex_cache(indices: &mut [u32], vertex_count: usize) -> Vec<usize> {
+    let triangle_count = indices.len() / 3;
+
+    // Build vertex to triangle adjacency, remaining triangles of a vertex are always kept at the
+    // front of its adjacency range
+    let mut remaining = vec![0u32; vertex_count];
+    for &index in indices.iter() {
+        remaining[index as usize] += 1;
+    }
+    let mut offsets = vec![0usize; vertex_count + 1];
+    for vertex in 0..vertex_count {
+        offsets[vertex + 1] = offsets[vertex] + remaining[vertex] as usize;
+    }
+    let mut adjacency = vec![0usize; offsets[vertex_count]];
+    let mut fill = offsets.clone();
+    for (triangle, triangle_indices) in indices.chunks_exact(3).enumerate() {
+        for &index in triangle_indices {
+            adjacency[fill[index as usize]] = triangle;
+            fill[index as usize] += 1;
+        }
+    }
+
+    let mut cache_positions = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = remaining
+        .iter()
+        .map(|&remaining| vertex_score(None, remaining))
+        .collect();
+    let mut triangle_scores: Vec<f32> = indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            triangle
+                .iter()
+                .map(|&index| vertex_scores[index as usize])
+                .sum()
+        })
+        .collect();
+    let mut emitted = vec![false; triangle_count];
+
+    let mut output = Vec::with_capacity(indices.len());
+    let mut order = Vec::with_capacity(triangle_count);
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut next_triangle = 0;
+    let mut best_triangle = best_triangle(&triangle_scores, &emitted);
+    while let Some(triangle) = best_triangle {
+        emitted[triangle] = true;
+        let triangle_indices = [
+            indices[triangle * 3],
+            indices[triangle * 3 + 1],
+            indices[triangle * 3 + 2],
+        ];
+        output.extend_from_slice(&triangle_indices);
+        order.push(triangle);
+
+        // Remove triangle from the adjacency of its vertices
+        for &index in &triangle_indices {
+            let vertex = index as usize;
+            let start = offsets[vertex];
+            let end = start + remaining[vertex] as usize;
+            if let Some(position) = adjacency[start..end].iter().position(|&t| t == triangle) {
+                adjacency.swap(start + position, end - 1);
+                remaining[vertex] -= 1;
+            }
+        }
+
+        // Move the triangle's vertices to the front of the simulated cache
+        let mut new_cache = Vec::with_capacity(CACHE_SIZE + 3);
+        for &index in &triangle_indices {
+            if !new_cache.contains(&index) {
+                new_cache.push(index);
+            }
+        }
+        new_cache.extend(
+            cache
+                .iter()
+                .copied()
+                .filter(|index| !triangle_indices.contains(index)),
+        );
+        let evicted = new_cache.split_off(new_cache.len().min(CACHE_SIZE));
+        for &index in &evicted {
+            cache_positions[index as usize] = None;
+        }
+        for (position, &index) in new_cache.iter().enumerate() {
+            cache_positions[index as usize] = Some(position);
+        }
+        cache = new_cache;
+
+        // Update scores of all touched vertices and their triangles, and pick the best one
+        for &index in cache.iter().chain(&evicted) {
+            let vertex = index as usize;
+            vertex_scores[vertex] = vertex_score(cache_positions[vertex], remaining[vertex]);
+        }
+        best_triangle = None;
+        let mut best_score = f32::MIN;
+        for &index in cache.iter().chain(&evicted) {
+            let vertex = index as usize;
+            let start = offsets[vertex];
+            let end = start + remaining[vertex] as usize;
+            for &triangle in &adjacency[start..end] {
+                let score = indices[triangle * 3..triangle * 3 + 3]
+                    .iter()
+                    .map(|&index| vertex_scores[index as usize])
+                    .sum();
+                triangle_scores[triangle] = score;
+                if score > best_score {
+                    best_score = score;
+                    best_triangle = Some(triangle);
+                }
+            }
+        }
+
+        // Without candidates in the cache continue with the next remaining triangle in input
+        // order, the cursor only moves forward
+        if best_triangle.is_none() {
+            while next_triangle < triangle_count && emitted[next_triangle] {
+                next_triangle += 1;
+            }
+            best_triangle = (next_triangle < triangle_count).then_some(next_triangle);
+        }
+    }
+
+    indices.copy_from_slice(&output);
+    order
+}
+
+/// Reorders vertices in the order they are first referenced, and remaps the indices accordingly.
+pub(crate) fn optimize_vertex_fetch<T: Copy>(indices: &mut [u32], vertices: &[T]) -> Vec<T> {
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut output = Vec::with_capacity(vertices.len());
+    for index in indices.iter_mut() {
+        let new_index = &mut remap[*index as usize];
+        if *new_index == u32::MAX {
+            *new_index = output.len() as u32;
+            output.push(vertices[*index as usize]);
+        }
+        *index = *new_index;
+    }
+
+    output
+}
+
+fn vertex_score(cache_position: Option<usize>, remaining: u32) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            (1.0 - (position - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(CACHE_DECAY_POWER)
+        }
+        None => 0.0,
+    };
+    let valence_score = VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER);
+
+    cache_score + valence_score
+}
+
+fn best_triangle(triangle_scores: &[f32], emitted: &[bool]) -> Option<usize> {
+    let mut best_triangle = None;
+    let mut best_score = f32::MIN;
+    for (triangle, &score) in triangle_scores.iter().enumerate() {
+        if !emitted[triangle] && score > best_score {
+            best_score = score;
+            best_triangle = Some(triangle);
+        }
+    }
+
+    best_triangle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vertex_cache_order() {
+        // Strip of quads, with the triangles in reverse
+        let mut indices = Vec::new();
+        for quad in (0..8).rev() {
+            indices.extend_from_slice(&[quad * 2, quad * 2 + 1, quad * 2 + 2]);
+            indices.extend_from_slice(&[quad * 2 + 1, quad * 2 + 3, quad * 2 + 2]);
+        }
+        let original = indices.clone();
+
+        let order = optimize_vertex_cache(&mut indices, 18);
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..16).collect::<Vec<_>>());
+        for (triangle, &previous) in order.iter().enumerate() {
+            assert_eq!(
+                indices[triangle * 3..triangle * 3 + 3],
+                original[previous * 3..previous * 3 + 3]
+            );
+        }
+    }
+
+    #[test]
+    fn disconnected_triangles() {
+        // No triangle shares a vertex, so every one after the first comes from the fallback
+        let mut indices: Vec<u32> = (0..3000).collect();
+        let order = optimize_vertex_cache(&mut indices, 3000);
+        assert_eq!(order, (0..1000).collect::<Vec<_>>());
+        assert_eq!(indices, (0..3000).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,139 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use bevy::{prelude::*, reflect::TypeUuid};
+
+/// Navigation data of a model, from the roadway and paths LODs.
+#[derive(TypeUuid, Clone, Debug, Default)]
+#[uuid = "c4e7a1d2-93b8-4f06-8e5a-1b6d0f2c9a37"]
+pub struct NavigationGraph {
+    /// Walkable triangles of the roadway LOD.
+    pub walkable: Vec<[Vec3; 3]>,
+    /// Points of the paths LOD.
+    pub nodes: Vec<NavigationNode>,
+    /// Undirected connections between nodes, from the edges of the paths LOD faces.
+    pub edges: Vec<[usize; 2]>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct NavigationNode {
+    pub position: Vec3,
+    pub kind: NavigationNodeKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NavigationNodeKind {
+    Path,
+    /// Entry point, `inN` selection.
+    Entry(u32),
+    /// Position inside, `posN` selection.
+    Position(u32),
+}
+
+impl NavigationGraph {
+    /// Returns all nodes connected to a node.
+    pub fn neighbors(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.edges.iter().filter_map(move |&[a, b]| {
+            if a == node {
+                Some(b)
+            } else if b == node {
+                Some(a)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the node of an entry point.
+    pub fn entry(&self, index: u32) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|node| node.kind == NavigationNodeKind::Entry(index))
+    }
+
+    /// Returns the node of a position.
+    pub fn position(&self, index: u32) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|node| node.kind == NavigationNodeKind::Position(index))
+    }
+
+    /// Returns the node closest to a model space position.
+    pub fn nearest_node(&self, position: Vec3) -> Option<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                a.position
+                    .distance_squared(position)
+                    .total_cmp(&b.position.distance_squared(position))
+            })
+            .map(|(index, _)| index)
+    }
+
+    /// Finds the shortest path between two nodes, including both.
+    pub fn find_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let goal = self.nodes.get(to)?.position;
+        let mut costs = vec![f32::INFINITY; self.nodes.len()];
+        let mut previous = vec![usize::MAX; self.nodes.len()];
+        let mut open = BinaryHeap::new();
+        *costs.get_mut(from)? = 0.0;
+        open.push(OpenNode {
+            node: from,
+            estimate: self.nodes[from].position.distance(goal),
+        });
+
+        while let Some(OpenNode { node, .. }) = open.pop() {
+            if node == to {
+                let mut path = vec![to];
+                while *path.last().unwrap() != from {
+                    path.push(previous[*path.last().unwrap()]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for neighbor in self.neighbors(node) {
+                let cost = costs[node]
+                    + self.nodes[node]
+                        .position
+                        .distance(self.nodes[neighbor].position);
+                if cost < costs[neighbor] {
+                    costs[neighbor] = cost;
+                    previous[neighbor] = node;
+                    open.push(OpenNode {
+                        node: neighbor,
+                        estimate: cost + self.nodes[neighbor].position.distance(goal),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+struct OpenNode {
+    node: usize,
+    estimate: f32,
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenNode {
+    // Reversed, as the heap pops the largest element first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
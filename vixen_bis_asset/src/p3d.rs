@@ -1,16 +1,66 @@
-use std::io::{Cursor, Read};
+use std::io::{Cursor, Read, Seek};
 
 use anyhow::{bail, Result};
 use bevy::{
-    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    asset::{AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadedAsset},
+    pbr::{NotShadowCaster, NotShadowReceiver},
     prelude::*,
-    render::mesh::{Indices, PrimitiveTopology},
+    render::mesh::{
+        skinning::{SkinnedMesh, SkinnedMeshInverseBindposes},
+        Indices, PrimitiveTopology, VertexAttributeValues,
+    },
+    render::render_resource::Face,
+    utils::{HashMap, HashSet},
 };
+use bitflags::bitflags;
 use byteorder::{LittleEndian, ReadBytesExt};
 use thiserror::Error;
 
+use crate::{
+    animation::{animate_sources, P3dAnimation, P3dAnimationKind, P3dAnimations},
+    config::{ConfigClass, ConfigCursor, ConfigValue},
+    faces::{P3dFace, P3dFaces, P3dTriangleFaces},
+    gltf::{GlbPrimitive, GlbWriter},
+    hitpoints::{HitSphere, HitZone, HitZones},
+    mesh::{optimize_vertex_cache, optimize_vertex_fetch},
+    navigation::{NavigationGraph, NavigationNode, NavigationNodeKind},
+    skeleton::P3dSkeleton,
+};
+
+/// Registers the P3D loader, the asset types it produces, and drives model.cfg animations.
+#[derive(Default)]
+pub struct P3dPlugin {
+    pub settings: P3dSettings,
+}
+
+impl Plugin for P3dPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<HitZones>()
+            .add_asset::<NavigationGraph>()
+            .add_asset::<P3dAnimations>()
+            .add_asset::<P3dDiagnostics>()
+            .add_asset::<P3dFaces>()
+            .add_asset::<P3dTriangleFaces>()
+            .register_type::<Handle<P3dAnimations>>()
+            .add_asset_loader(P3dLoader::new(self.settings.clone()))
+            .add_system(animate_sources);
+    }
+}
+
+/// Bohemia P3D asset loader.
+///
+/// Besides a mesh for each LOD, additional asset types are produced which have to be registered,
+/// use [`P3dPlugin`] instead of registering the loader directly.
 #[derive(Default)]
-pub struct P3dLoader;
+pub struct P3dLoader {
+    pub settings: P3dSettings,
+}
+
+impl P3dLoader {
+    pub fn new(settings: P3dSettings) -> Self {
+        Self { settings }
+    }
+}
 
 impl AssetLoader for P3dLoader {
     fn load<'a>(
@@ -18,7 +68,7 @@ impl AssetLoader for P3dLoader {
         bytes: &'a [u8],
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<()>> {
-        Box::pin(async move { load_mlod(bytes, load_context).await })
+        Box::pin(async move { load_mlod(bytes, load_context, &self.settings).await })
     }
 
     fn extensions(&self) -> &[&str] {
@@ -26,96 +76,1320 @@ impl AssetLoader for P3dLoader {
     }
 }
 
-#[derive(Error, Debug)]
-enum P3dError {
-    #[error("invalid magic")]
-    InvalidMagic,
-    #[error("unknown version: {0}")]
-    UnknownVersion(String),
+/// Settings for [`P3dLoader`].
+///
+/// Bohemia models are authored in a left-handed, Y-up coordinate system (X right, Z forward),
+/// while Bevy is right-handed (X right, -Z forward), therefore a faithful conversion has to mirror
+/// one of the horizontal axes, which in turn flips the winding order of all faces.
+#[derive(Clone, Debug)]
+pub struct P3dSettings {
+    /// Conversion from Bohemia's left-handed coordinate system.
+    pub coordinate_system: P3dCoordinateSystem,
+    /// Winding order of the front faces in the source data.
+    pub winding: P3dWinding,
+    /// Negate the normals, MLOD normals point inwards.
+    pub flip_normals: bool,
+    /// Uniform scale applied to all positions.
+    pub scale: f32,
+    /// Filter deciding which LODs are loaded, skipped LODs keep their label index.
+    pub lods: fn(P3dLod) -> bool,
+    /// Use the most detailed shadow volume LOD as shadow caster of the scene.
+    pub shadow_volumes: bool,
+    /// Radius of all hit zone spheres, the actual radii are defined per zone by config.cpp, see
+    /// [`HitZones`].
+    pub hit_point_radius: f32,
+    /// Merge face corners with identical position, normal and uv into shared vertices.
+    pub weld_vertices: bool,
+    /// Reorder triangles and vertices for post-transform vertex cache and fetch efficiency.
+    pub optimize_vertex_cache: bool,
+    /// Handling of malformed faces and unknown versions.
+    pub validation: P3dValidation,
 }
 
-async fn load_mlod<'a, 'b>(bytes: &'a [u8], load_context: &'a mut LoadContext<'b>) -> Result<()> {
-    let file = Mlod::read_from(&mut Cursor::new(bytes.to_vec()))?;
+impl Default for P3dSettings {
+    fn default() -> Self {
+        Self {
+            coordinate_system: P3dCoordinateSystem::Native,
+            winding: P3dWinding::Clockwise,
+            flip_normals: true,
+            scale: 1.0,
+            lods: |_| true,
+            shadow_volumes: true,
+            hit_point_radius: 0.3,
+            weld_vertices: true,
+            optimize_vertex_cache: true,
+            validation: P3dValidation::Strict,
+        }
+    }
+}
 
-    for (i, model) in file.0.iter().enumerate() {
-        let mut positions = Vec::new();
-        let mut normals = Vec::new();
-        let mut uvs = Vec::new();
-
-        let mut indices = Vec::new();
-        let mut index = 0;
-
-        for face in &model.faces {
-            // Add indices (CCW winding order)
-            match face.vertex_count {
-                3 => {
-                    indices.push(index + 2);
-                    indices.push(index + 1);
-                    indices.push(index + 0);
-                    index += 3;
-                }
-                4 => {
-                    indices.push(index + 3);
-                    indices.push(index + 2);
-                    indices.push(index + 1);
-                    indices.push(index + 1);
-                    indices.push(index + 0);
-                    indices.push(index + 3);
-                    index += 4;
-                }
-                _ => {}
+/// Conversion from Bohemia's left-handed coordinate system.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum P3dCoordinateSystem {
+    /// Keep coordinates as-is, which results in a mirrored model.
+    #[default]
+    Native,
+    /// Mirror the X axis, forward stays +Z.
+    MirrorX,
+    /// Mirror the Z axis, forward becomes -Z like in Bevy.
+    MirrorZ,
+}
+
+impl P3dSettings {
+    /// Converts a point into Bevy's coordinate system, and applies the scale.
+    fn convert_point(&self, position: [f32; 3]) -> Vec3 {
+        Vec3::from(
+            self.coordinate_system
+                .convert(position.map(|value| value * self.scale)),
+        )
+    }
+}
+
+impl P3dCoordinateSystem {
+    fn convert(&self, value: [f32; 3]) -> [f32; 3] {
+        match self {
+            Self::Native => value,
+            Self::MirrorX => [-value[0], value[1], value[2]],
+            Self::MirrorZ => [value[0], value[1], -value[2]],
+        }
+    }
+
+    fn is_mirrored(&self) -> bool {
+        *self != Self::Native
+    }
+}
+
+/// Winding order of front faces.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum P3dWinding {
+    #[default]
+    Clockwise,
+    CounterClockwise,
+}
+
+/// Kind of a LOD, derived from its resolution.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum P3dLod {
+    /// Visual LOD, lower resolutions are more detailed.
+    Resolution(f32),
+    ViewGunner,
+    ViewPilot,
+    ViewCargo,
+    /// Shadow volume, with the resolution relative to 10000.
+    ShadowVolume(f32),
+    /// Edit LOD, with the resolution relative to 20000.
+    Edit(f32),
+    Geometry,
+    GeometryBuoyancy,
+    GeometryPhysx,
+    Memory,
+    LandContact,
+    Roadway,
+    Paths,
+    HitPoints,
+    ViewGeometry,
+    FireGeometry,
+    /// Any other special LOD.
+    Special(f32),
+}
+
+impl P3dLod {
+    pub fn from_resolution(resolution: f32) -> Self {
+        // Special LODs use large resolutions, which are not exactly representable
+        let is = |value: f32| (resolution - value).abs() <= value * 1e-3;
+        match resolution {
+            _ if resolution < 1000.0 => Self::Resolution(resolution),
+            _ if is(1000.0) => Self::ViewGunner,
+            _ if is(1100.0) => Self::ViewPilot,
+            _ if is(1200.0) => Self::ViewCargo,
+            _ if (10000.0..20000.0).contains(&resolution) => {
+                Self::ShadowVolume(resolution - 10000.0)
+            }
+            _ if (20000.0..30000.0).contains(&resolution) => Self::Edit(resolution - 20000.0),
+            _ if is(1e13) => Self::Geometry,
+            _ if is(2e13) => Self::GeometryBuoyancy,
+            _ if is(4e13) => Self::GeometryPhysx,
+            _ if is(1e15) => Self::Memory,
+            _ if is(2e15) => Self::LandContact,
+            _ if is(3e15) => Self::Roadway,
+            _ if is(4e15) => Self::Paths,
+            _ if is(5e15) => Self::HitPoints,
+            _ if is(6e15) => Self::ViewGeometry,
+            _ if is(7e15) => Self::FireGeometry,
+            _ => Self::Special(resolution),
+        }
+    }
+
+    /// Returns true for LODs which are meant to be rendered.
+    pub fn is_visual(&self) -> bool {
+        matches!(
+            self,
+            Self::Resolution(_) | Self::ViewGunner | Self::ViewPilot | Self::ViewCargo
+        )
+    }
+}
+
+bitflags! {
+    /// Flags of a P3D point.
+    pub struct P3dPointFlags: u32 {
+        const ON_LAND = 0x1;
+        const UNDER_LAND = 0x2;
+        const ABOVE_LAND = 0x4;
+        const KEEP_LAND = 0x8;
+        const LAND_MASK = 0xF;
+
+        /// Shining.
+        const NO_LIGHT = 0x10;
+        /// Always in shadow.
+        const AMBIENT = 0x20;
+        const FULL_LIGHT = 0x40;
+        const HALF_LIGHT = 0x80;
+        const LIGHT_MASK = 0xF0;
+
+        const DECAL = 0x100;
+        const VERTICAL_DECAL = 0x200;
+        const DECAL_MASK = 0x300;
+
+        const NO_FOG = 0x1000;
+        const SKY_FOG = 0x2000;
+        const FOG_MASK = 0x3000;
+
+        const USER_MASK = 0xFF0000;
+
+        const HIDDEN = 0x1000000;
+        const SPECIAL_MASK = 0xF000000;
+    }
+}
+
+impl P3dPointFlags {
+    pub fn user_value(&self) -> u8 {
+        ((self.bits() & Self::USER_MASK.bits()) >> 16) as u8
+    }
+}
+
+bitflags! {
+    /// Flags of a P3D face.
+    pub struct P3dFaceFlags: u32 {
+        /// Shining.
+        const NO_LIGHT = 0x1;
+        /// Always in shadow.
+        const AMBIENT = 0x2;
+        const FULL_LIGHT = 0x4;
+        const BOTH_SIDES_LIGHT = 0x20;
+        const SKY_LIGHT = 0x80;
+        const REVERSE_LIGHT = 0x100000;
+        const FLAT_LIGHT = 0x200000;
+        const LIGHT_MASK = 0x3000A7;
+
+        const IS_SHADOW = 0x8;
+        const NO_SHADOW = 0x10;
+        const SHADOW_MASK = 0x18;
+
+        const Z_BIAS_MASK = 0x300;
+
+        const BEGIN_FAN = 0x400;
+        const BEGIN_STRIP = 0x800;
+        const CONTINUE_FAN = 0xC00;
+        const FAN_STRIP_MASK = 0xC00;
+
+        const DISABLE_TEXTURE_MERGE = 0x1000000;
+
+        const USER_MASK = 0xFE000000;
+    }
+}
+
+impl P3dFaceFlags {
+    pub fn z_bias(&self) -> u8 {
+        ((self.bits() & Self::Z_BIAS_MASK.bits()) >> 8) as u8
+    }
+
+    pub fn user_value(&self) -> u8 {
+        ((self.bits() & Self::USER_MASK.bits()) >> 25) as u8
+    }
+}
+
+/// Handling of recoverable problems, like malformed faces, out-of-range indices and unknown
+/// versions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum P3dValidation {
+    /// Fail loading.
+    #[default]
+    Strict,
+    /// Skip malformed faces and continue, problems are reported in [`P3dDiagnostics`].
+    Lenient,
+}
+
+/// Problems encountered while loading a P3D in lenient mode.
+#[derive(TypeUuid, Clone, Debug, Default)]
+#[uuid = "e3a97c52-18d6-4b0f-92c4-7d5e1f8a6b29"]
+pub struct P3dDiagnostics(pub Vec<P3dError>);
+
+/// Error while reading a P3D, LOD and face indices are zero-based, offsets are in bytes from the
+/// start of the file.
+#[derive(Error, Clone, Debug)]
+pub enum P3dError {
+    #[error("invalid magic at {offset:#x}")]
+    InvalidMagic { offset: u64 },
+    #[error("unknown version {version} at {offset:#x}")]
+    UnknownVersion { version: String, offset: u64 },
+    #[error("unexpected end of file in LOD {lod:?}, face {face:?} at {offset:#x}")]
+    UnexpectedEndOfFile {
+        lod: Option<usize>,
+        face: Option<usize>,
+        offset: u64,
+    },
+    #[error("invalid vertex count {count} in LOD {lod}, face {face} at {offset:#x}")]
+    InvalidVertexCount {
+        lod: usize,
+        face: usize,
+        count: u32,
+        offset: u64,
+    },
+    #[error("point index {index} out of bounds in LOD {lod}, face {face} at {offset:#x}")]
+    PointOutOfBounds {
+        lod: usize,
+        face: usize,
+        index: u32,
+        offset: u64,
+    },
+    #[error("normal index {index} out of bounds in LOD {lod}, face {face} at {offset:#x}")]
+    NormalOutOfBounds {
+        lod: usize,
+        face: usize,
+        index: u32,
+        offset: u64,
+    },
+}
+
+/// Structure of an MLOD, for inspection without building meshes.
+#[derive(Clone, Debug, Default)]
+pub struct MlodInfo {
+    pub lods: Vec<MlodLodInfo>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MlodLodInfo {
+    pub resolution: f32,
+    pub kind: P3dLod,
+    pub points: usize,
+    pub normals: usize,
+    pub faces: usize,
+    /// Distinct textures of all faces, in order of appearance.
+    pub textures: Vec<String>,
+    /// Distinct materials of all faces, in order of appearance.
+    pub materials: Vec<String>,
+    pub selections: Vec<String>,
+    /// Named properties, e.g. `class` or `map`.
+    pub properties: Vec<(String, String)>,
+}
+
+impl MlodInfo {
+    pub fn read(
+        bytes: &[u8],
+        validation: P3dValidation,
+    ) -> Result<(Self, P3dDiagnostics), P3dError> {
+        let (file, diagnostics) = Mlod::read_from(&mut Cursor::new(bytes), validation)?;
+        let lods = file
+            .0
+            .iter()
+            .map(|model| MlodLodInfo {
+                resolution: model.resolution,
+                kind: P3dLod::from_resolution(model.resolution),
+                points: model.points.len(),
+                normals: model.normals.len(),
+                faces: model.faces.len(),
+                textures: distinct(model.faces.iter().map(|face| face.texture_name.as_str())),
+                materials: distinct(model.faces.iter().map(|face| face.material_name.as_str())),
+                selections: model.selections().map(str::to_string).collect(),
+                properties: model.properties(),
+            })
+            .collect();
+
+        Ok((Self { lods }, diagnostics))
+    }
+}
+
+/// Header of a binarized ODOL, LODs are not read beyond their resolutions.
+#[derive(Clone, Debug, Default)]
+pub struct OdolInfo {
+    pub version: u32,
+    /// Path prefix of the model, since version 58.
+    pub prefix: String,
+    pub lods: Vec<(f32, P3dLod)>,
+}
+
+impl OdolInfo {
+    pub fn read(bytes: &[u8]) -> Result<Self, P3dError> {
+        let input = &mut Cursor::new(bytes);
+        if input
+            .read_u32::<LittleEndian>()
+            .map_err(truncated(None, None, 0))?
+            != u32::from_le_bytes(*b"ODOL")
+        {
+            return Err(P3dError::InvalidMagic { offset: 0 });
+        }
+        let version = input
+            .read_u32::<LittleEndian>()
+            .map_err(truncated(None, None, 4))?;
+
+        // Application id and prefix of newer versions precede the LODs
+        let offset = position(input);
+        if version >= 59 {
+            input
+                .read_u32::<LittleEndian>()
+                .map_err(truncated(None, None, offset))?;
+        }
+        let prefix = if version >= 58 {
+            read_asciiz(input).map_err(truncated(None, None, offset))?
+        } else {
+            String::new()
+        };
+
+        let offset = position(input);
+        let lod_count = input
+            .read_u32::<LittleEndian>()
+            .map_err(truncated(None, None, offset))?;
+        let mut lods = Vec::with_capacity(lod_count.min(MAXIMUM_PREALLOCATION) as usize);
+        for lod in 0..lod_count as usize {
+            let resolution =
+                input
+                    .read_f32::<LittleEndian>()
+                    .map_err(truncated(Some(lod), None, offset))?;
+            lods.push((resolution, P3dLod::from_resolution(resolution)));
+        }
+
+        Ok(Self {
+            version,
+            prefix,
+            lods,
+        })
+    }
+}
+
+/// Returns the non-empty names, without duplicates, in order of appearance.
+fn distinct<'a>(names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut distinct: Vec<String> = Vec::new();
+    for name in names {
+        if !name.is_empty() && !distinct.iter().any(|distinct| distinct == name) {
+            distinct.push(name.to_string());
+        }
+    }
+    distinct
+}
+
+/// Reads an MLOD and builds the geometry of all LODs without a load context, which allows
+/// validating files up front, returns the problems skipped in lenient mode.
+pub fn check_mlod(bytes: &[u8], settings: &P3dSettings) -> Result<P3dDiagnostics, P3dError> {
+    let (file, diagnostics) = Mlod::read_from(&mut Cursor::new(bytes), settings.validation)?;
+    let memory_lod = file
+        .0
+        .iter()
+        .find(|model| P3dLod::from_resolution(model.resolution) == P3dLod::Memory);
+    for model in &file.0 {
+        let faces: Vec<_> = (0..model.faces.len()).collect();
+        build_vertices(model, &faces, settings);
+        for (_, faces) in batch_faces(model) {
+            build_vertices(model, &faces, settings);
+        }
+        match P3dLod::from_resolution(model.resolution) {
+            P3dLod::HitPoints => {
+                read_hit_zones(model, settings);
+            }
+            P3dLod::Roadway => {
+                read_navigation(Some(model), None, memory_lod, settings);
+            }
+            P3dLod::Paths => {
+                read_navigation(None, Some(model), memory_lod, settings);
             }
+            _ => {}
+        }
+    }
+
+    Ok(diagnostics)
+}
+
+/// Converts an MLOD into binary glTF, with a mesh for each LOD whose batches are primitives, and
+/// the most detailed visual LOD in the scene.
+///
+/// Textures are referenced by the uri returned for their path, skeletons, animations, hit zones
+/// and navigation are not converted.
+pub fn mlod_to_glb(
+    bytes: &[u8],
+    settings: &P3dSettings,
+    texture_uri: impl Fn(&str) -> String,
+) -> Result<(Vec<u8>, P3dDiagnostics)> {
+    let (file, diagnostics) = Mlod::read_from(&mut Cursor::new(bytes), settings.validation)?;
+    let mut writer = GlbWriter::default();
+    let mut materials = HashMap::default();
+    let mut scene_mesh = None;
+    for model in &file.0 {
+        let lod = P3dLod::from_resolution(model.resolution);
+        if !(settings.lods)(lod) {
+            continue;
+        }
+
+        let batches: Vec<_> = batch_faces(model)
+            .into_iter()
+            .map(|(batch, faces)| {
+                let material = *materials.entry(batch.clone()).or_insert_with(|| {
+                    let (material, texture) = batch.standard_material();
+                    let uri = texture.map(|path| texture_uri(&path));
+                    writer.add_material(&material, uri.as_deref())
+                });
+                let (vertices, indices, _) = build_vertices(model, &faces, settings);
+                let positions: Vec<_> = vertices.iter().map(|vertex| vertex.position).collect();
+                let normals: Vec<_> = vertices.iter().map(|vertex| vertex.normal).collect();
+                let uvs: Vec<_> = vertices.iter().map(|vertex| vertex.uv).collect();
+                (positions, normals, uvs, indices, material)
+            })
+            .collect();
+        let primitives: Vec<_> = batches
+            .iter()
+            .map(
+                |(positions, normals, uvs, indices, material)| GlbPrimitive {
+                    positions,
+                    normals,
+                    uvs,
+                    indices,
+                    material: *material,
+                },
+            )
+            .collect();
+
+        let mesh = writer.add_mesh(&format!("{lod:?}"), &primitives);
+        if scene_mesh.is_none() && lod.is_visual() {
+            scene_mesh = mesh;
+        }
+    }
+
+    let glb = writer.finish(scene_mesh)?;
+    Ok((glb, diagnostics))
+}
+
+async fn load_mlod<'a, 'b>(
+    bytes: &'a [u8],
+    load_context: &'a mut LoadContext<'b>,
+    settings: &'a P3dSettings,
+) -> Result<()> {
+    let (file, diagnostics) = Mlod::read_from(&mut Cursor::new(bytes), settings.validation)?;
+    for diagnostic in &diagnostics.0 {
+        warn!("{:?}: {diagnostic}", load_context.path());
+    }
+    load_context.set_labeled_asset("Diagnostics", LoadedAsset::new(diagnostics));
+
+    // Skeleton and animations are defined by the closest model.cfg, using the file stem as model
+    // name
+    let config = load_model_cfg(load_context).await;
+    let model_name = load_context
+        .path()
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let skeleton = config
+        .as_ref()
+        .and_then(|config| P3dSkeleton::from_model_cfg(config, &model_name));
+
+    // Memory LOD contains points referenced by other LODs and model.cfg, regardless of the filter
+    let memory_lod = file
+        .0
+        .iter()
+        .find(|model| P3dLod::from_resolution(model.resolution) == P3dLod::Memory);
 
-            // Add vertices
-            for vertex in &face.vertices[..face.vertex_count as usize] {
-                positions.push(
-                    model
-                        .points
-                        .get((vertex.point_index) as usize)
-                        .unwrap()
-                        .position,
+    let mut scene_lod = None;
+    let mut scene_batches = Vec::new();
+    let mut shadow_mesh = None;
+    let mut roadway_lod = None;
+    let mut paths_lod = None;
+    for (i, model) in file.0.iter().enumerate() {
+        let lod = P3dLod::from_resolution(model.resolution);
+        if !(settings.lods)(lod) {
+            continue;
+        }
+
+        match lod {
+            P3dLod::HitPoints => {
+                load_context.set_labeled_asset(
+                    "HitZones",
+                    LoadedAsset::new(read_hit_zones(model, settings)),
                 );
-                let normal = model.normals.get((vertex.normal_index) as usize).unwrap();
-                normals.push([-normal[0], -normal[1], -normal[2]]);
-                uvs.push(vertex.uv);
             }
+            P3dLod::Roadway => roadway_lod = Some(model),
+            P3dLod::Paths => paths_lod = Some(model),
+            _ => {}
         }
 
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-        mesh.set_indices(Some(Indices::U32(indices)));
+        // Triangles of each mesh map to the source faces, the shadow volume shares them with its
+        // LOD
+        load_context.set_labeled_asset(
+            format!("{i}/Faces").as_str(),
+            LoadedAsset::new(model.source_faces()),
+        );
+        let faces: Vec<_> = (0..model.faces.len()).collect();
+        let (vertices, indices, triangle_faces) = build_vertices(model, &faces, settings);
+        let mesh = build_mesh(&vertices, indices);
+        load_context.set_labeled_asset(
+            format!("{i}/Triangles").as_str(),
+            LoadedAsset::new(triangle_faces),
+        );
+
+        // The most detailed visual LOD is used for the scene, and the most detailed shadow volume
+        // as its shadow caster, both are skinned if there is a skeleton
+        let is_scene_lod = scene_lod.is_none() && lod.is_visual();
+        let is_shadow_lod = settings.shadow_volumes
+            && shadow_mesh.is_none()
+            && matches!(lod, P3dLod::ShadowVolume(_));
+        let point_joints = match &skeleton {
+            Some(skeleton) if is_scene_lod || is_shadow_lod => Some(point_joints(model, skeleton)),
+            _ => None,
+        };
+
+        if is_shadow_lod {
+            shadow_mesh = Some(load_context.set_labeled_asset(
+                "ShadowVolume",
+                LoadedAsset::new(match &point_joints {
+                    Some(point_joints) => build_skinned_mesh(mesh.clone(), &vertices, point_joints),
+                    None => mesh.clone(),
+                }),
+            ));
+        }
+        if is_scene_lod {
+            scene_lod = Some(model);
 
+            // Split into batches with the same texture and render properties
+            for (batch_index, (batch, faces)) in batch_faces(model).into_iter().enumerate() {
+                let (vertices, indices, triangle_faces) = build_vertices(model, &faces, settings);
+                let mut mesh = build_mesh(&vertices, indices);
+                if let Some(point_joints) = &point_joints {
+                    mesh = build_skinned_mesh(mesh, &vertices, point_joints);
+                }
+                load_context.set_labeled_asset(
+                    format!("{i}/Batch{batch_index}/Triangles").as_str(),
+                    LoadedAsset::new(triangle_faces),
+                );
+                scene_batches.push((
+                    batch,
+                    load_context.set_labeled_asset(
+                        format!("{i}/Batch{batch_index}").as_str(),
+                        LoadedAsset::new(mesh),
+                    ),
+                ));
+            }
+        }
         load_context.set_labeled_asset(i.to_string().as_str(), LoadedAsset::new(mesh));
     }
 
+    if roadway_lod.is_some() || paths_lod.is_some() {
+        load_context.set_labeled_asset(
+            "Navigation",
+            LoadedAsset::new(read_navigation(
+                roadway_lod,
+                paths_lod,
+                memory_lod,
+                settings,
+            )),
+        );
+    }
+
+    if !scene_batches.is_empty() {
+        let mut world = World::default();
+        let root = world.spawn(SpatialBundle::VISIBLE_IDENTITY).id();
+        let mut meshes = Vec::new();
+        for (batch_index, (batch, mesh)) in scene_batches.into_iter().enumerate() {
+            let material = batch.material(load_context);
+            let material =
+                load_context.set_labeled_asset(format!("Material{batch_index}").as_str(), material);
+            let mut entity = world.spawn(PbrBundle {
+                mesh,
+                material,
+                ..default()
+            });
+
+            // Shadow volumes replace the visual LOD as shadow caster
+            if batch.no_shadow || shadow_mesh.is_some() {
+                entity.insert(NotShadowCaster);
+            }
+            meshes.push(entity.id());
+        }
+
+        // Shadow volumes are masked out entirely in the main pass, but still rendered into shadow
+        // maps
+        if let Some(shadow_mesh) = shadow_mesh {
+            let shadow_material = load_context.set_labeled_asset(
+                "ShadowVolumeMaterial",
+                LoadedAsset::new(StandardMaterial {
+                    base_color: Color::NONE,
+                    alpha_mode: AlphaMode::Mask(0.5),
+                    unlit: true,
+                    ..default()
+                }),
+            );
+            meshes.push(
+                world
+                    .spawn((
+                        PbrBundle {
+                            mesh: shadow_mesh,
+                            material: shadow_material,
+                            ..default()
+                        },
+                        NotShadowReceiver,
+                    ))
+                    .id(),
+            );
+        }
+        world.entity_mut(root).push_children(&meshes);
+
+        // Bones have no rest transform, therefore all joints start at the origin, joint 0 is the
+        // root for unassigned vertices
+        if let Some(skeleton) = &skeleton {
+            let mut joints = vec![root];
+            for bone in &skeleton.bones {
+                joints.push(
+                    world
+                        .spawn((
+                            SpatialBundle::VISIBLE_IDENTITY,
+                            Name::new(bone.name.clone()),
+                        ))
+                        .id(),
+                );
+            }
+            for (bone_index, bone) in skeleton.bones.iter().enumerate() {
+                let parent = bone.parent.map_or(root, |parent| joints[parent + 1]);
+                world
+                    .entity_mut(parent)
+                    .push_children(&[joints[bone_index + 1]]);
+            }
+
+            let inverse_bindposes = load_context.set_labeled_asset(
+                "InverseBindposes",
+                LoadedAsset::new(SkinnedMeshInverseBindposes::from(vec![
+                    Mat4::IDENTITY;
+                    joints.len()
+                ])),
+            );
+            for &mesh in &meshes {
+                world.entity_mut(mesh).insert(SkinnedMesh {
+                    inverse_bindposes: inverse_bindposes.clone(),
+                    joints: joints.clone(),
+                });
+            }
+
+            // Animations are only applied to skinned models, as they animate bones
+            let animations = config.as_ref().map_or_else(Vec::new, |config| {
+                read_animations(config, &model_name, memory_lod, scene_lod, settings)
+            });
+            if !animations.is_empty() {
+                let animations = load_context
+                    .set_labeled_asset("Animations", LoadedAsset::new(P3dAnimations(animations)));
+                world.entity_mut(root).insert(animations);
+            }
+        }
+
+        load_context.set_labeled_asset("Scene", LoadedAsset::new(Scene::new(world)));
+    }
+
     Ok(())
 }
 
+/// Reads the closest model.cfg, starting in the directory of the model.
+async fn load_model_cfg(load_context: &LoadContext<'_>) -> Option<ConfigClass> {
+    let mut directory = load_context.path().parent();
+    while let Some(path) = directory {
+        if let Ok(bytes) = load_context.read_asset_bytes(path.join("model.cfg")).await {
+            return match ConfigClass::from_bytes(&bytes) {
+                Ok(config) => Some(config),
+                Err(error) => {
+                    warn!("Failed to parse {:?}: {error}", path.join("model.cfg"));
+                    None
+                }
+            };
+        }
+        directory = path.parent();
+    }
+
+    None
+}
+
+fn read_hit_zones(model: &P3dm, settings: &P3dSettings) -> HitZones {
+    HitZones(
+        model
+            .selections()
+            .map(|name| HitZone {
+                name: name.to_string(),
+                spheres: model
+                    .selection_positions(name)
+                    .into_iter()
+                    .map(|center| HitSphere {
+                        center: settings.convert_point(center.to_array()),
+                        radius: settings.hit_point_radius * settings.scale,
+                    })
+                    .collect(),
+            })
+            .filter(|zone| !zone.spheres.is_empty())
+            .collect(),
+    )
+}
+
+/// Reads walkable triangles from the roadway LOD, and the node graph from the paths LOD, entry
+/// points and positions are selections of either the paths or memory LOD.
+fn read_navigation(
+    roadway_lod: Option<&P3dm>,
+    paths_lod: Option<&P3dm>,
+    memory_lod: Option<&P3dm>,
+    settings: &P3dSettings,
+) -> NavigationGraph {
+    let mut navigation = NavigationGraph::default();
+
+    if let Some(model) = roadway_lod {
+        for face in model.faces.iter().filter(|face| face.valid) {
+            let positions: Vec<_> = face
+                .vertices()
+                .iter()
+                .filter_map(|vertex| model.points.get(vertex.point_index as usize))
+                .map(|point| settings.convert_point(point.position))
+                .collect();
+            for i in 2..positions.len() {
+                navigation
+                    .walkable
+                    .push([positions[0], positions[i - 1], positions[i]]);
+            }
+        }
+    }
+
+    if let Some(model) = paths_lod {
+        navigation.nodes = model
+            .points
+            .iter()
+            .map(|point| NavigationNode {
+                position: settings.convert_point(point.position),
+                kind: NavigationNodeKind::Path,
+            })
+            .collect();
+
+        let mut edges = HashSet::default();
+        for face in model.faces.iter().filter(|face| face.valid) {
+            let vertices = face.vertices();
+            for (i, vertex) in vertices.iter().enumerate() {
+                let a = vertex.point_index as usize;
+                let b = vertices[(i + 1) % vertices.len()].point_index as usize;
+                if a != b && a < navigation.nodes.len() && b < navigation.nodes.len() {
+                    edges.insert([a.min(b), a.max(b)]);
+                }
+            }
+        }
+        navigation.edges = edges.into_iter().collect();
+        navigation.edges.sort_unstable();
+
+        // Entry points and positions, memory points are mapped to the nearest node
+        for (lod, is_memory) in [(Some(model), false), (memory_lod, true)] {
+            let Some(lod) = lod else {
+                continue;
+            };
+            for name in lod.selections() {
+                let kind = if let Some(index) = selection_number(name, "in") {
+                    NavigationNodeKind::Entry(index)
+                } else if let Some(index) = selection_number(name, "pos") {
+                    NavigationNodeKind::Position(index)
+                } else {
+                    continue;
+                };
+                if navigation.nodes.iter().any(|node| node.kind == kind) {
+                    continue;
+                }
+
+                let node = if is_memory {
+                    lod.selection_positions(name).first().and_then(|&position| {
+                        navigation.nearest_node(settings.convert_point(position.to_array()))
+                    })
+                } else {
+                    lod.selection_point_indices(name).first().copied()
+                };
+                if let Some(node) = node {
+                    navigation.nodes[node].kind = kind;
+                }
+            }
+        }
+    }
+
+    navigation
+}
+
+/// Parses the number of a numbered selection, e.g. `in1` or `pos2`.
+fn selection_number(name: &str, prefix: &str) -> Option<u32> {
+    if !name.get(..prefix.len())?.eq_ignore_ascii_case(prefix) {
+        return None;
+    }
+
+    name[prefix.len()..].parse().ok()
+}
+
+/// Reads the animations of a model, axes are either defined in the memory LOD or the visual LOD.
+fn read_animations(
+    config: &ConfigClass,
+    model_name: &str,
+    memory_lod: Option<&P3dm>,
+    visual_lod: Option<&P3dm>,
+    settings: &P3dSettings,
+) -> Vec<P3dAnimation> {
+    let Some(animations) = ConfigCursor::new(config)
+        .class("CfgModels")
+        .and_then(|models| models.class(model_name))
+        .and_then(|model| model.class("Animations"))
+    else {
+        return Vec::new();
+    };
+
+    // Mirroring inverts the direction of rotations
+    let convert = |value: Vec3| Vec3::from(settings.coordinate_system.convert(value.to_array()));
+    let angle_sign = if settings.coordinate_system.is_mirrored() {
+        -1.0
+    } else {
+        1.0
+    };
+
+    animations
+        .classes()
+        .iter()
+        .filter_map(|class| {
+            let lod = if class
+                .value("memory")
+                .and_then(ConfigValue::as_i32)
+                .unwrap_or(1)
+                != 0
+            {
+                memory_lod
+            } else {
+                visual_lod
+            };
+            let mut animation = P3dAnimation::from_config(class, |name| {
+                Some(lod?.selection_positions(name)).filter(|points| !points.is_empty())
+            })?;
+            match &mut animation.kind {
+                P3dAnimationKind::Rotation {
+                    origin,
+                    axis,
+                    angle0,
+                    angle1,
+                } => {
+                    *origin = settings.convert_point(origin.to_array());
+                    *axis = convert(*axis);
+                    *angle0 *= angle_sign;
+                    *angle1 *= angle_sign;
+                }
+                P3dAnimationKind::Translation { axis, .. } => {
+                    *axis = convert(*axis * settings.scale);
+                }
+                P3dAnimationKind::Hide { .. } => {}
+            }
+
+            Some(animation)
+        })
+        .collect()
+}
+
+fn build_vertices(
+    model: &P3dm,
+    faces: &[usize],
+    settings: &P3dSettings,
+) -> (Vec<Vertex>, Vec<u32>, P3dTriangleFaces) {
+    // Mirroring an axis flips the winding order
+    let reverse_winding =
+        (settings.winding == P3dWinding::Clockwise) != settings.coordinate_system.is_mirrored();
+    let normal_sign = if settings.flip_normals { -1.0 } else { 1.0 };
+
+    let mut vertices = Vec::new();
+    let mut vertex_indices = HashMap::default();
+
+    let mut indices = Vec::new();
+    let mut triangle_faces = Vec::new();
+
+    for (face_index, face) in faces
+        .iter()
+        .map(|&face| (face, &model.faces[face]))
+        .filter(|(_, face)| face.valid)
+    {
+        // Add vertices, and reuse identical ones if welding is enabled
+        let mut face_indices = [0; 4];
+        let face_indices = &mut face_indices[..face.vertices().len()];
+        for (face_index, vertex) in face_indices.iter_mut().zip(face.vertices()) {
+            // Indices of valid faces are checked when reading
+            let position = model.points[vertex.point_index as usize].position;
+            let normal = &model.normals[vertex.normal_index as usize];
+            let vertex = Vertex {
+                point: vertex.point_index,
+                position: settings.convert_point(position).to_array(),
+                normal: settings
+                    .coordinate_system
+                    .convert(normal.map(|value| value * normal_sign)),
+                uv: vertex.uv,
+            };
+            *face_index = if settings.weld_vertices {
+                *vertex_indices.entry(vertex.key()).or_insert_with(|| {
+                    vertices.push(vertex);
+                    vertices.len() as u32 - 1
+                })
+            } else {
+                vertices.push(vertex);
+                vertices.len() as u32 - 1
+            };
+        }
+
+        // Add indices as a triangle fan (CCW winding order)
+        if reverse_winding {
+            face_indices.reverse();
+        }
+        for i in 2..face_indices.len() {
+            indices.push(face_indices[0]);
+            indices.push(face_indices[i - 1]);
+            indices.push(face_indices[i]);
+            triangle_faces.push(face_index as u32);
+        }
+    }
+
+    if settings.optimize_vertex_cache {
+        let order = optimize_vertex_cache(&mut indices, vertices.len());
+        triangle_faces = order
+            .into_iter()
+            .map(|triangle| triangle_faces[triangle])
+            .collect();
+        vertices = optimize_vertex_fetch(&mut indices, &vertices);
+    }
+
+    (vertices, indices, P3dTriangleFaces(triangle_faces))
+}
+
+/// Render properties of a batch of faces.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Batch {
+    texture_name: String,
+    unlit: bool,
+    double_sided: bool,
+    no_shadow: bool,
+    /// Depth bias of decals, zero for regular faces.
+    decal: u8,
+}
+
+impl Batch {
+    fn new(model: &P3dm, face: &P3dmFace) -> Self {
+        let point_flags: Vec<_> = face
+            .vertices()
+            .iter()
+            .filter_map(|vertex| model.points.get(vertex.point_index as usize))
+            .map(|point| point.flags)
+            .collect();
+        let is_lit = |flags: P3dPointFlags| {
+            !flags.intersects(P3dPointFlags::NO_LIGHT | P3dPointFlags::FULL_LIGHT)
+        };
+        let is_decal = point_flags
+            .iter()
+            .any(|flags| flags.intersects(P3dPointFlags::DECAL_MASK));
+
+        Self {
+            texture_name: face.texture_name.clone(),
+            // Shining and fully lit faces are not affected by lighting
+            unlit: face
+                .flags
+                .intersects(P3dFaceFlags::NO_LIGHT | P3dFaceFlags::FULL_LIGHT)
+                || (!point_flags.is_empty() && !point_flags.iter().copied().any(is_lit)),
+            double_sided: face.flags.contains(P3dFaceFlags::BOTH_SIDES_LIGHT),
+            no_shadow: face.flags.contains(P3dFaceFlags::NO_SHADOW),
+            decal: if is_decal {
+                face.flags.z_bias().max(1)
+            } else {
+                face.flags.z_bias()
+            },
+        }
+    }
+
+    fn material(&self, load_context: &LoadContext) -> LoadedAsset<StandardMaterial> {
+        let (mut material, texture) = self.standard_material();
+        if let Some(path) = texture {
+            material.base_color_texture =
+                Some(load_context.get_handle(AssetPath::new(path.clone().into(), None)));
+
+            return LoadedAsset::new(material).with_dependency(AssetPath::new(path.into(), None));
+        }
+
+        LoadedAsset::new(material)
+    }
+
+    /// Returns the material without texture, and the normalized path of the texture.
+    fn standard_material(&self) -> (StandardMaterial, Option<String>) {
+        let mut material = StandardMaterial {
+            unlit: self.unlit,
+            double_sided: self.double_sided,
+            cull_mode: if self.double_sided {
+                None
+            } else {
+                Some(Face::Back)
+            },
+            ..default()
+        };
+        if self.decal != 0 {
+            material.alpha_mode = AlphaMode::Blend;
+            material.depth_bias = self.decal as f32;
+        }
+
+        // Textures are either procedural or paths relative to the root
+        let texture_name = self.texture_name.trim();
+        if let Some(color) = procedural_color(texture_name) {
+            material.base_color = color;
+        } else if !texture_name.is_empty() {
+            let path = texture_name
+                .trim_start_matches('\\')
+                .replace('\\', "/")
+                .to_ascii_lowercase();
+
+            // Color textures with alpha channel are suffixed with _ca
+            if material.alpha_mode == AlphaMode::Opaque && path.contains("_ca.") {
+                material.alpha_mode = AlphaMode::Mask(0.5);
+            }
+            return (material, Some(path));
+        }
+
+        (material, None)
+    }
+}
+
+/// Groups the faces of a model by their batch, in order of appearance.
+fn batch_faces(model: &P3dm) -> Vec<(Batch, Vec<usize>)> {
+    let mut batches: Vec<(Batch, Vec<usize>)> = Vec::new();
+    let mut batch_indices = HashMap::default();
+    for (face_index, face) in model.faces.iter().enumerate() {
+        if !face.valid {
+            continue;
+        }
+        let batch = Batch::new(model, face);
+        let batch_index = *batch_indices.entry(batch.clone()).or_insert_with(|| {
+            batches.push((batch, Vec::new()));
+            batches.len() - 1
+        });
+        batches[batch_index].1.push(face_index);
+    }
+
+    batches
+}
+
+/// Parses procedural color textures, e.g. `#(argb,8,8,3)color(1,0,0,1,co)`.
+fn procedural_color(texture_name: &str) -> Option<Color> {
+    let arguments = texture_name
+        .strip_prefix("#(")?
+        .split_once(")color(")?
+        .1
+        .strip_suffix(')')?;
+    let mut values = arguments
+        .split(',')
+        .map(|value| value.trim().parse::<f32>());
+    Some(Color::rgba(
+        values.next()?.ok()?,
+        values.next()?.ok()?,
+        values.next()?.ok()?,
+        values.next()?.ok()?,
+    ))
+}
+
+fn build_mesh(vertices: &[Vertex], indices: Vec<u32>) -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vertices
+            .iter()
+            .map(|vertex| vertex.position)
+            .collect::<Vec<_>>(),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vertices
+            .iter()
+            .map(|vertex| vertex.normal)
+            .collect::<Vec<_>>(),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        vertices.iter().map(|vertex| vertex.uv).collect::<Vec<_>>(),
+    );
+    mesh.set_indices(Some(
+        // Use 16-bit indices if all vertices are addressable
+        if vertices.len() <= u16::MAX as usize + 1 {
+            Indices::U16(indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            Indices::U32(indices)
+        },
+    ));
+
+    mesh
+}
+
+fn build_skinned_mesh(
+    mut mesh: Mesh,
+    vertices: &[Vertex],
+    point_joints: &[([u16; 4], [f32; 4])],
+) -> Mesh {
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_JOINT_INDEX,
+        VertexAttributeValues::Uint16x4(
+            vertices
+                .iter()
+                .map(|vertex| point_joints[vertex.point as usize].0)
+                .collect(),
+        ),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_JOINT_WEIGHT,
+        vertices
+            .iter()
+            .map(|vertex| point_joints[vertex.point as usize].1)
+            .collect::<Vec<_>>(),
+    );
+
+    mesh
+}
+
+/// Computes up to four joint indices and weights per point, where joint 0 is the root and bone i
+/// is joint i + 1.
+fn point_joints(model: &P3dm, skeleton: &P3dSkeleton) -> Vec<([u16; 4], [f32; 4])> {
+    let mut point_bones = vec![Vec::new(); model.points.len()];
+    for (bone_index, bone) in skeleton.bones.iter().enumerate() {
+        if let Some(weights) = model.selection_points(&bone.name) {
+            for (bones, &weight) in point_bones.iter_mut().zip(weights) {
+                let weight = selection_weight(weight);
+                if weight > 0.0 {
+                    bones.push((bone_index as u16 + 1, weight));
+                }
+            }
+        }
+    }
+
+    point_bones
+        .into_iter()
+        .map(|mut bones: Vec<(u16, f32)>| {
+            let mut joint_indices = [0; 4];
+            let mut joint_weights = [0.0; 4];
+            if bones.is_empty() {
+                joint_weights[0] = 1.0;
+            } else {
+                // Keep the most influential bones, and normalize their weights
+                bones.sort_by(|a, b| b.1.total_cmp(&a.1));
+                bones.truncate(4);
+                let total_weight: f32 = bones.iter().map(|(_, weight)| weight).sum();
+                for (i, (joint_index, weight)) in bones.into_iter().enumerate() {
+                    joint_indices[i] = joint_index;
+                    joint_weights[i] = weight / total_weight;
+                }
+            }
+
+            (joint_indices, joint_weights)
+        })
+        .collect()
+}
+
+/// Decodes a selection weight, 0 is unselected, 1 is fully selected, and all other values are
+/// decreasing partial weights.
+fn selection_weight(value: u8) -> f32 {
+    match value {
+        0 => 0.0,
+        1 => 1.0,
+        _ => (256 - value as u32) as f32 / 255.0,
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Vertex {
+    point: u32,
+    position: [f32; 3],
+    normal: [f32; 3],
+    uv: [f32; 2],
+}
+
+impl Vertex {
+    /// Bitwise identity of the source point and all attributes, used for welding.
+    fn key(&self) -> [u32; 9] {
+        [
+            self.point,
+            self.position[0].to_bits(),
+            self.position[1].to_bits(),
+            self.position[2].to_bits(),
+            self.normal[0].to_bits(),
+            self.normal[1].to_bits(),
+            self.normal[2].to_bits(),
+            self.uv[0].to_bits(),
+            self.uv[1].to_bits(),
+        ]
+    }
+}
+
 #[derive(Debug)]
 struct Mlod(Vec<P3dm>);
 
 impl Mlod {
-    fn read_from<R: Read>(input: &mut R) -> Result<Self> {
-        if input.read_u32::<LittleEndian>()? != u32::from_be_bytes(*b"MLOD") {
-            bail!(P3dError::InvalidMagic)
+    /// Reads all LODs, problems which can be recovered from are returned as diagnostics in lenient
+    /// mode.
+    fn read_from<R: Read + Seek>(
+        input: &mut R,
+        validation: P3dValidation,
+    ) -> Result<(Self, P3dDiagnostics), P3dError> {
+        let mut diagnostics = P3dDiagnostics::default();
+        let offset = position(input);
+        if input
+            .read_u32::<LittleEndian>()
+            .map_err(truncated(None, None, offset))?
+            != u32::from_le_bytes(*b"MLOD")
+        {
+            return Err(P3dError::InvalidMagic { offset });
         }
-        let version = input.read_u32::<LittleEndian>()?;
+        let version = input
+            .read_u32::<LittleEndian>()
+            .map_err(truncated(None, None, offset))?;
         if version != 0x101 {
-            bail!(P3dError::UnknownVersion(version.to_string()))
+            diagnostics.report(
+                validation,
+                P3dError::UnknownVersion {
+                    version: version.to_string(),
+                    offset,
+                },
+            )?;
         }
 
-        let lod_count = input.read_u32::<LittleEndian>()?;
-        let mut lods = Vec::with_capacity(lod_count as usize);
-        for _ in 0..lod_count {
-            lods.push(P3dm::read_from(input)?);
+        let lod_count = input
+            .read_u32::<LittleEndian>()
+            .map_err(truncated(None, None, offset))?;
+        let mut lods = Vec::with_capacity(lod_count.min(MAXIMUM_PREALLOCATION) as usize);
+        for lod in 0..lod_count as usize {
+            lods.push(P3dm::read_from(input, lod, validation, &mut diagnostics)?);
         }
 
-        Ok(Self(lods))
+        Ok((Self(lods), diagnostics))
     }
 }
 
+impl P3dDiagnostics {
+    /// Fails in strict mode, otherwise records the problem.
+    fn report(&mut self, validation: P3dValidation, error: P3dError) -> Result<(), P3dError> {
+        match validation {
+            P3dValidation::Strict => Err(error),
+            P3dValidation::Lenient => {
+                self.0.push(error);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Upper bound of elements allocated up front, as counts are not trusted.
+const MAXIMUM_PREALLOCATION: u32 = 0x10000;
+
+pub(crate) fn position<R: Seek>(input: &mut R) -> u64 {
+    input.stream_position().unwrap_or_default()
+}
+
+fn truncated<E>(
+    lod: Option<usize>,
+    face: Option<usize>,
+    offset: u64,
+) -> impl FnOnce(E) -> P3dError {
+    move |_| P3dError::UnexpectedEndOfFile { lod, face, offset }
+}
+
 #[derive(Debug)]
 struct P3dm {
     flags: u32,
@@ -127,50 +1401,170 @@ struct P3dm {
 }
 
 impl P3dm {
-    fn read_from<R: Read>(input: &mut R) -> Result<Self> {
-        if input.read_u32::<LittleEndian>()? != u32::from_be_bytes(*b"P3DM") {
-            bail!(P3dError::InvalidMagic)
-        }
-        let major_version = input.read_u32::<LittleEndian>()?;
-        let minor_version = input.read_u32::<LittleEndian>()?;
-        if major_version != 0x1C && minor_version != 0x101 {
-            bail!(P3dError::UnknownVersion(format!(
-                "{major_version}.{minor_version}"
-            )))
-        }
-
-        let point_count = input.read_u32::<LittleEndian>()?;
-        let normal_count = input.read_u32::<LittleEndian>()?;
-        let face_count = input.read_u32::<LittleEndian>()?;
-        let flags = input.read_u32::<LittleEndian>()?;
-        let mut points = Vec::with_capacity(point_count as usize);
+    /// Returns the named properties, stored as two zero-padded strings of 64 bytes.
+    fn properties(&self) -> Vec<(String, String)> {
+        let string = |data: &[u8]| {
+            let length = data
+                .iter()
+                .position(|&byte| byte == 0)
+                .unwrap_or(data.len());
+            String::from_utf8_lossy(&data[..length]).into_owned()
+        };
+        self.tags
+            .iter()
+            .filter(|tag| tag.name == "#Property#" && tag.data.len() >= 128)
+            .map(|tag| (string(&tag.data[..64]), string(&tag.data[64..128])))
+            .collect()
+    }
+
+    /// Returns the names of all named selections.
+    fn selections(&self) -> impl Iterator<Item = &str> {
+        self.tags
+            .iter()
+            .filter(|tag| !tag.name.starts_with('#'))
+            .map(|tag| tag.name.as_str())
+    }
+
+    /// Returns the point weights of a named selection.
+    fn selection_points(&self, name: &str) -> Option<&[u8]> {
+        self.tags
+            .iter()
+            .find(|tag| !tag.name.starts_with('#') && tag.name.eq_ignore_ascii_case(name))
+            .map(|tag| &tag.data[..self.points.len().min(tag.data.len())])
+    }
+
+    /// Returns the indices of all points in a named selection.
+    fn selection_point_indices(&self, name: &str) -> Vec<usize> {
+        self.selection_points(name)
+            .map(|weights| {
+                weights
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, weight)| **weight != 0)
+                    .map(|(index, _)| index)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the positions of all points in a named selection.
+    fn selection_positions(&self, name: &str) -> Vec<Vec3> {
+        self.selection_point_indices(name)
+            .into_iter()
+            .map(|index| Vec3::from(self.points[index].position))
+            .collect()
+    }
+
+    /// Returns the faces with their selections, whose weights follow the point weights.
+    fn source_faces(&self) -> P3dFaces {
+        let selections: Vec<_> = self
+            .tags
+            .iter()
+            .filter(|tag| !tag.name.starts_with('#'))
+            .collect();
+        P3dFaces(
+            self.faces
+                .iter()
+                .enumerate()
+                .map(|(face_index, face)| P3dFace {
+                    flags: face.flags,
+                    texture: face.texture_name.clone(),
+                    material: face.material_name.clone(),
+                    selections: selections
+                        .iter()
+                        .filter(|tag| {
+                            tag.data
+                                .get(self.points.len() + face_index)
+                                .is_some_and(|&weight| weight != 0)
+                        })
+                        .map(|tag| tag.name.clone())
+                        .collect(),
+                })
+                .collect(),
+        )
+    }
+
+    fn read_from<R: Read + Seek>(
+        input: &mut R,
+        lod: usize,
+        validation: P3dValidation,
+        diagnostics: &mut P3dDiagnostics,
+    ) -> Result<Self, P3dError> {
+        let mut offset = position(input);
+        if input
+            .read_u32::<LittleEndian>()
+            .map_err(truncated(Some(lod), None, offset))?
+            != u32::from_le_bytes(*b"P3DM")
+        {
+            return Err(P3dError::InvalidMagic { offset });
+        }
+        let mut header = [0; 6];
+        input
+            .read_u32_into::<LittleEndian>(&mut header)
+            .map_err(truncated(Some(lod), None, offset))?;
+        let [major_version, minor_version, point_count, normal_count, face_count, flags] = header;
+        if major_version != 0x1C || !matches!(minor_version, 0x100 | 0x101) {
+            diagnostics.report(
+                validation,
+                P3dError::UnknownVersion {
+                    version: format!("{major_version}.{minor_version}"),
+                    offset,
+                },
+            )?;
+        }
+
+        offset = position(input);
+        let mut points = Vec::with_capacity(point_count.min(MAXIMUM_PREALLOCATION) as usize);
         for _ in 0..point_count {
-            points.push(P3dmPoint::read_from(input)?);
+            points.push(P3dmPoint::read_from(input).map_err(truncated(Some(lod), None, offset))?);
         }
-        let mut normals = Vec::with_capacity(normal_count as usize);
+        offset = position(input);
+        let mut normals = Vec::with_capacity(normal_count.min(MAXIMUM_PREALLOCATION) as usize);
         for _ in 0..normal_count {
-            normals.push(core::array::from_fn(|_| {
-                input.read_f32::<LittleEndian>().unwrap()
-            }));
+            let mut normal = [0.0; 3];
+            input
+                .read_f32_into::<LittleEndian>(&mut normal)
+                .map_err(truncated(Some(lod), None, offset))?;
+            normals.push(normal);
         }
-        let mut faces = Vec::with_capacity(face_count as usize);
-        for _ in 0..face_count {
-            faces.push(P3dmFace::read_from(input)?);
+        let mut faces = Vec::with_capacity(face_count.min(MAXIMUM_PREALLOCATION) as usize);
+        for face_index in 0..face_count as usize {
+            offset = position(input);
+            let mut face = P3dmFace::read_from(input).map_err(truncated(
+                Some(lod),
+                Some(face_index),
+                offset,
+            ))?;
+            if let Some(error) = face.validate(lod, face_index, offset, &points, &normals) {
+                diagnostics.report(validation, error)?;
+                face.valid = false;
+            }
+            faces.push(face);
         }
 
-        if input.read_u32::<LittleEndian>()? != u32::from_be_bytes(*b"TAGG") {
-            bail!(P3dError::InvalidMagic)
+        offset = position(input);
+        if input
+            .read_u32::<LittleEndian>()
+            .map_err(truncated(Some(lod), None, offset))?
+            != u32::from_le_bytes(*b"TAGG")
+        {
+            return Err(P3dError::InvalidMagic { offset });
         }
         let mut tags = Vec::new();
         loop {
-            let tag: P3dmTag = P3dmTag::read_from(input)?;
+            offset = position(input);
+            let tag = P3dmTag::read_from(input).map_err(truncated(Some(lod), None, offset))?;
             if tag.name == "#EndOfFile#" {
                 break;
             }
             tags.push(tag);
         }
 
-        let resolution = input.read_f32::<LittleEndian>()?;
+        offset = position(input);
+        let resolution =
+            input
+                .read_f32::<LittleEndian>()
+                .map_err(truncated(Some(lod), None, offset))?;
 
         Ok(Self {
             flags,
@@ -186,14 +1580,18 @@ impl P3dm {
 #[derive(Debug)]
 struct P3dmPoint {
     position: [f32; 3],
-    flags: u32,
+    flags: P3dPointFlags,
 }
 
 impl P3dmPoint {
     fn read_from<R: Read>(input: &mut R) -> Result<Self> {
         Ok(Self {
-            position: core::array::from_fn(|_| input.read_f32::<LittleEndian>().unwrap()),
-            flags: input.read_u32::<LittleEndian>()?,
+            position: [
+                input.read_f32::<LittleEndian>()?,
+                input.read_f32::<LittleEndian>()?,
+                input.read_f32::<LittleEndian>()?,
+            ],
+            flags: P3dPointFlags::from_bits_truncate(input.read_u32::<LittleEndian>()?),
         })
     }
 }
@@ -201,8 +1599,10 @@ impl P3dmPoint {
 #[derive(Debug)]
 struct P3dmFace {
     vertex_count: u32,
+    /// Faces failing validation are kept to preserve face indices, but not built.
+    valid: bool,
     vertices: [P3dmVertex; 4],
-    flags: u32,
+    flags: P3dFaceFlags,
     texture_name: String,
     material_name: String,
 }
@@ -211,12 +1611,61 @@ impl P3dmFace {
     fn read_from<R: Read>(input: &mut R) -> Result<Self> {
         Ok(Self {
             vertex_count: input.read_u32::<LittleEndian>()?,
-            vertices: core::array::from_fn(|_| P3dmVertex::read_from(input).unwrap()),
-            flags: input.read_u32::<LittleEndian>()?,
+            valid: true,
+            vertices: [
+                P3dmVertex::read_from(input)?,
+                P3dmVertex::read_from(input)?,
+                P3dmVertex::read_from(input)?,
+                P3dmVertex::read_from(input)?,
+            ],
+            flags: P3dFaceFlags::from_bits_truncate(input.read_u32::<LittleEndian>()?),
             texture_name: read_asciiz(input)?,
             material_name: read_asciiz(input)?,
         })
     }
+
+    /// Returns the used vertices, at most four even if the vertex count is invalid.
+    fn vertices(&self) -> &[P3dmVertex] {
+        &self.vertices[..(self.vertex_count as usize).min(self.vertices.len())]
+    }
+
+    /// Returns the first problem of the face, indices must reference read points and normals.
+    fn validate(
+        &self,
+        lod: usize,
+        face: usize,
+        offset: u64,
+        points: &[P3dmPoint],
+        normals: &[[f32; 3]],
+    ) -> Option<P3dError> {
+        if !(3..=4).contains(&self.vertex_count) {
+            return Some(P3dError::InvalidVertexCount {
+                lod,
+                face,
+                count: self.vertex_count,
+                offset,
+            });
+        }
+        for vertex in self.vertices() {
+            if vertex.point_index as usize >= points.len() {
+                return Some(P3dError::PointOutOfBounds {
+                    lod,
+                    face,
+                    index: vertex.point_index,
+                    offset,
+                });
+            }
+            if vertex.normal_index as usize >= normals.len() {
+                return Some(P3dError::NormalOutOfBounds {
+                    lod,
+                    face,
+                    index: vertex.normal_index,
+                    offset,
+                });
+            }
+        }
+        None
+    }
 }
 
 #[derive(Debug)]
@@ -231,7 +1680,10 @@ impl P3dmVertex {
         Ok(Self {
             point_index: input.read_u32::<LittleEndian>()?,
             normal_index: input.read_u32::<LittleEndian>()?,
-            uv: core::array::from_fn(|_| input.read_f32::<LittleEndian>().unwrap()),
+            uv: [
+                input.read_f32::<LittleEndian>()?,
+                input.read_f32::<LittleEndian>()?,
+            ],
         })
     }
 }
@@ -249,8 +1701,13 @@ impl P3dmTag {
             active: input.read_u8()? != 0,
             name: read_asciiz(input)?,
             data: {
-                let mut data = vec![0; input.read_u32::<LittleEndian>()? as usize];
-                input.read_exact(&mut data)?;
+                // Read incrementally, as the length is not trusted
+                let length = input.read_u32::<LittleEndian>()? as u64;
+                let mut data = Vec::new();
+                input.by_ref().take(length).read_to_end(&mut data)?;
+                if data.len() as u64 != length {
+                    bail!(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))
+                }
                 data
             },
         })
@@ -258,7 +1715,7 @@ impl P3dmTag {
 }
 
 #[inline]
-fn read_asciiz<R: Read>(input: &mut R) -> Result<String> {
+pub(crate) fn read_asciiz<R: Read>(input: &mut R) -> Result<String> {
     let mut data = Vec::new();
     loop {
         let value = input.read_u8()?;
@@ -270,3 +1727,63 @@ fn read_asciiz<R: Read>(input: &mut R) -> Result<String> {
 
     Ok(data.iter().collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hit_zones_scale() {
+        let model = P3dm {
+            flags: 0,
+            points: vec![P3dmPoint {
+                position: [1.0, 2.0, 3.0],
+                flags: P3dPointFlags::empty(),
+            }],
+            normals: Vec::new(),
+            faces: Vec::new(),
+            tags: vec![P3dmTag {
+                active: true,
+                name: "engine".to_string(),
+                data: vec![1],
+            }],
+            resolution: 0.0,
+        };
+        let settings = P3dSettings {
+            scale: 2.0,
+            hit_point_radius: 0.5,
+            ..default()
+        };
+
+        // Both the center and the radius are scaled
+        let hit_zones = read_hit_zones(&model, &settings);
+        assert_eq!(
+            hit_zones
+                .zones_at(Vec3::new(2.0, 4.0, 6.9))
+                .collect::<Vec<_>>(),
+            ["engine"]
+        );
+        assert_eq!(hit_zones.zones_at(Vec3::new(2.0, 4.0, 7.1)).count(), 0);
+    }
+
+    #[test]
+    fn odol_header() {
+        let mut data = b"ODOL".to_vec();
+        data.extend_from_slice(&73u32.to_le_bytes());
+        data.extend_from_slice(&107410u32.to_le_bytes());
+        data.extend_from_slice(b"a3\\data_f\0");
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&1.0f32.to_le_bytes());
+        data.extend_from_slice(&1e13f32.to_le_bytes());
+
+        let odol = OdolInfo::read(&data).unwrap();
+        assert_eq!(odol.version, 73);
+        assert_eq!(odol.prefix, "a3\\data_f");
+        assert_eq!(
+            odol.lods,
+            [(1.0, P3dLod::Resolution(1.0)), (1e13, P3dLod::Geometry)]
+        );
+
+        assert!(OdolInfo::read(&data[..data.len() - 1]).is_err());
+    }
+}
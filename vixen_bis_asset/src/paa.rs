@@ -1,14 +1,26 @@
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
 
 use anyhow::{bail, Result};
 use bevy::{
-    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    asset::{AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadedAsset},
     prelude::*,
-    render::render_resource::{Extent3d, TextureFormat},
+    render::render_resource::{
+        Extent3d, TextureFormat, TextureViewDescriptor, TextureViewDimension,
+    },
 };
 use byteorder::{LittleEndian, ReadBytesExt};
 use thiserror::Error;
 
+use crate::{
+    compression::lzss_decompress,
+    config::{ConfigClass, ConfigCursor, ConfigValue},
+    ktx2::write_ktx2,
+    p3d::position,
+};
+
 #[derive(Default)]
 pub struct PaaLoader;
 
@@ -26,78 +38,383 @@ impl AssetLoader for PaaLoader {
     }
 }
 
+/// Sizes are 16-bit, so the chain of a 32768 pixel wide PAA ends after 16 mipmaps.
+const MAXIMUM_MIPMAP_COUNT: usize = 16;
+
+/// Error while reading a PAA, mipmap indices are zero-based, offsets are in bytes from the start of
+/// the file.
 #[derive(Error, Debug)]
-enum PaaError {
-    #[error("unknown tag: {0}")]
-    UnknownTag(String),
-    #[error("invalid tag")]
-    InvalidTag,
+pub enum PaaError {
+    #[error("unknown type {0:#06x}")]
+    UnknownType(u16),
+    #[error("unknown tag {name} at {offset:#x}")]
+    UnknownTag { name: String, offset: u64 },
+    #[error("invalid length {length} of tag {name} at {offset:#x}")]
+    InvalidTagLength {
+        name: String,
+        length: u32,
+        offset: u64,
+    },
+    #[error("unexpected end of file in mipmap {mipmap:?} at {offset:#x}")]
+    UnexpectedEndOfFile { mipmap: Option<usize>, offset: u64 },
+    #[error("invalid size {width}x{height} of mipmap {mipmap} at {offset:#x}")]
+    InvalidMipmapSize {
+        mipmap: usize,
+        width: u16,
+        height: u16,
+        offset: u64,
+    },
+    #[error("invalid compressed data in mipmap {mipmap} at {offset:#x}")]
+    InvalidCompressedData { mipmap: usize, offset: u64 },
+    #[error("mipmap {mipmap} has {actual} bytes instead of {expected} at {offset:#x}")]
+    DataSizeMismatch {
+        mipmap: usize,
+        expected: usize,
+        actual: usize,
+        offset: u64,
+    },
+    #[error("no mipmaps")]
+    NoMipmaps,
+    #[error("more than {MAXIMUM_MIPMAP_COUNT} mipmaps at {offset:#x}")]
+    TooManyMipmaps { offset: u64 },
+    #[error("size {width}x{height} is not a multiple of the block size")]
+    UnalignedSize { width: u16, height: u16 },
+    #[error("invalid size {width}x{height} of mipmap {mipmap}, expected {expected_width}x{expected_height}")]
+    InvalidMipmapChain {
+        mipmap: usize,
+        width: u16,
+        height: u16,
+        expected_width: u16,
+        expected_height: u16,
+    },
+    #[error("image data of mipmap {mipmap} has {actual} bytes instead of {expected}")]
+    ImageSizeMismatch {
+        mipmap: usize,
+        expected: usize,
+        actual: usize,
+    },
 }
 
 async fn load_paa<'a, 'b>(bytes: &'a [u8], load_context: &'a mut LoadContext<'b>) -> Result<()> {
-    let file = Paa::read_from(&mut Cursor::new(bytes.to_vec()))?;
+    load_context.set_default_asset(LoadedAsset::new(read_image(bytes)?));
+
+    Ok(())
+}
+
+fn read_image(bytes: &[u8]) -> Result<Image> {
+    let file = Paa::read_from(&mut Cursor::new(bytes))?;
+
+    // Reading fails without mipmaps
+    let (width, height) = (file.mipmaps[0].width, file.mipmaps[0].height);
+    let texture_format = file.format.texture_format();
+    let info = texture_format.describe();
+    let block = (
+        info.block_dimensions.0 as usize,
+        info.block_dimensions.1 as usize,
+        info.block_size as usize,
+    );
+    if width as usize % block.0 != 0 || height as usize % block.1 != 0 {
+        bail!(PaaError::UnalignedSize { width, height })
+    }
+
+    // Each mipmap has to halve the size of the previous one, and have the exact length expected by
+    // wgpu
+    let mut data = Vec::new();
+    let mip_level_count = file.mipmaps.len();
+    for (index, mipmap) in file.mipmaps.into_iter().enumerate() {
+        let expected_width = (width >> index).max(1);
+        let expected_height = (height >> index).max(1);
+        if (mipmap.width, mipmap.height) != (expected_width, expected_height) {
+            bail!(PaaError::InvalidMipmapChain {
+                mipmap: index,
+                width: mipmap.width,
+                height: mipmap.height,
+                expected_width,
+                expected_height,
+            })
+        }
+
+        let mut mipmap_data = file.format.decode(mipmap.data, &file.palette);
+        let expected = data_size(block, mipmap.width as usize, mipmap.height as usize);
+        if mipmap_data.len() != expected {
+            bail!(PaaError::ImageSizeMismatch {
+                mipmap: index,
+                expected,
+                actual: mipmap_data.len(),
+            })
+        }
+        data.append(&mut mipmap_data);
+    }
 
     let mut image = Image::default();
-    image.texture_descriptor.format = file.type_.texture_format();
-    image.texture_descriptor.mip_level_count = file.mipmaps.len() as u32;
-    let (width, height) = {
-        let PaaMipmap {
-            width,
-            height,
-            data: _,
-        } = file.mipmaps.first().unwrap();
-        (*width as u32, *height as u32)
-    };
+    image.texture_descriptor.format = texture_format;
+    image.texture_descriptor.mip_level_count = mip_level_count as u32;
     image.texture_descriptor.size = Extent3d {
-        width,
-        height,
+        width: width as u32,
+        height: height as u32,
         depth_or_array_layers: 1,
     };
+    image.data = data;
+
+    Ok(image)
+}
+
+/// Converts a PAA into KTX2, keeping compressed blocks and mipmaps.
+pub fn paa_to_ktx2(bytes: &[u8]) -> Result<Vec<u8>> {
+    write_ktx2(&read_image(bytes)?)
+}
+
+/// Loader of texture arrays and cubemaps from multiple PAAs, described by a config manifest with
+/// the `texarray` extension:
+///
+/// ```cpp
+/// dimension = "cube"; // or "array"
+/// layers[] = {"sky_px.paa", "sky_nx.paa", "sky_py.paa", "sky_ny.paa", "sky_pz.paa", "sky_nz.paa"};
+/// ```
+///
+/// Paths are relative to the manifest. Cubemaps without layers use the faces `<stem>_px.paa`,
+/// `<stem>_nx.paa`, `<stem>_py.paa`, `<stem>_ny.paa`, `<stem>_pz.paa` and `<stem>_nz.paa` next to
+/// the manifest. All layers need the same format, size and mipmaps, compressed blocks are kept.
+#[derive(Default)]
+pub struct PaaArrayLoader;
+
+impl AssetLoader for PaaArrayLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move { load_paa_array(bytes, load_context).await })
+    }
 
-    let mut data: Vec<_> = Vec::with_capacity((width * height * 2) as usize);
-    for mut mipmap in file.mipmaps {
-        data.append(&mut mipmap.data);
+    fn extensions(&self) -> &[&str] {
+        &["texarray"]
     }
-    image.data = data;
+}
 
-    load_context.set_default_asset(LoadedAsset::new(image));
+#[derive(Error, Debug)]
+enum PaaArrayError {
+    #[error("unknown dimension: {0}")]
+    UnknownDimension(String),
+    #[error("no layers")]
+    NoLayers,
+    #[error("cubemaps need 6 layers, or a multiple for cubemap arrays, not {0}")]
+    InvalidCubeLayerCount(usize),
+    #[error("format, size or mipmaps of {0:?} differ from the first layer")]
+    LayerMismatch(PathBuf),
+}
+
+/// Cube faces in the layer order of wgpu, as suffixes of the manifest stem.
+const CUBE_FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+async fn load_paa_array<'a, 'b>(
+    bytes: &'a [u8],
+    load_context: &'a mut LoadContext<'b>,
+) -> Result<()> {
+    let config = ConfigClass::from_bytes(bytes)?;
+    let manifest = ConfigCursor::new(&config);
+    let cube = match manifest.value("dimension").and_then(ConfigValue::as_str) {
+        None | Some("array") => false,
+        Some("cube") => true,
+        Some(dimension) => bail!(PaaArrayError::UnknownDimension(dimension.to_string())),
+    };
+
+    let directory = load_context
+        .path()
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let mut paths: Vec<_> = manifest
+        .value("layers")
+        .and_then(ConfigValue::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(ConfigValue::as_str)
+        .map(|path| directory.join(path.replace('\\', "/")))
+        .collect();
+    if paths.is_empty() && cube {
+        let stem = load_context
+            .path()
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        paths = CUBE_FACES
+            .iter()
+            .map(|face| directory.join(format!("{stem}_{face}.paa")))
+            .collect();
+    }
+    if paths.is_empty() {
+        bail!(PaaArrayError::NoLayers)
+    }
+    if cube && paths.len() % CUBE_FACES.len() != 0 {
+        bail!(PaaArrayError::InvalidCubeLayerCount(paths.len()))
+    }
+
+    // Layers are stored one after another, each with all of its mipmaps
+    let mut image: Option<Image> = None;
+    for path in &paths {
+        let layer = read_image(&load_context.read_asset_bytes(path).await?)?;
+        match &mut image {
+            None => image = Some(layer),
+            Some(image) => {
+                let descriptor = &image.texture_descriptor;
+                if layer.texture_descriptor.format != descriptor.format
+                    || layer.texture_descriptor.size.width != descriptor.size.width
+                    || layer.texture_descriptor.size.height != descriptor.size.height
+                    || layer.texture_descriptor.mip_level_count != descriptor.mip_level_count
+                {
+                    bail!(PaaArrayError::LayerMismatch(path.clone()))
+                }
+                image.data.extend_from_slice(&layer.data);
+            }
+        }
+    }
+    let Some(mut image) = image else {
+        bail!(PaaArrayError::NoLayers)
+    };
+    image.texture_descriptor.size.depth_or_array_layers = paths.len() as u32;
+    image.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(match (cube, paths.len()) {
+            (true, 6) => TextureViewDimension::Cube,
+            (true, _) => TextureViewDimension::CubeArray,
+            (false, _) => TextureViewDimension::D2Array,
+        }),
+        ..default()
+    });
+
+    load_context.set_default_asset(
+        LoadedAsset::new(image).with_dependencies(paths.into_iter().map(AssetPath::from).collect()),
+    );
 
     Ok(())
 }
 
+/// Header, tags and mipmaps of a PAA, for inspection without creating an image.
+#[derive(Clone, Debug)]
+pub struct PaaInfo {
+    pub format: PaaFormat,
+    /// Tag names and their values, e.g. `AVGCTAGG` and `#ff808080`.
+    pub tags: Vec<(String, String)>,
+    pub palette: usize,
+    pub mipmaps: Vec<PaaMipmapInfo>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PaaMipmapInfo {
+    pub width: u16,
+    pub height: u16,
+    /// Size of the data after decompression.
+    pub size: usize,
+}
+
+impl PaaInfo {
+    pub fn read(bytes: &[u8]) -> Result<Self, PaaError> {
+        let file = Paa::read_from(&mut Cursor::new(bytes))?;
+        Ok(Self {
+            format: file.format,
+            tags: file.tags.iter().map(PaaTag::describe).collect(),
+            palette: file.palette.len(),
+            mipmaps: file
+                .mipmaps
+                .iter()
+                .map(|mipmap| PaaMipmapInfo {
+                    width: mipmap.width,
+                    height: mipmap.height,
+                    size: mipmap.data.len(),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Reads the size of the largest mipmap from the header, without reading any mipmap data.
+pub(crate) fn paa_size(bytes: &[u8]) -> Option<UVec2> {
+    let mut input = bytes;
+    PaaFormat::read_from(&mut input).ok()?;
+
+    // Skip the tags, which are prefixed with their length, and the palette
+    while input.starts_with(b"GGAT") {
+        let length = u32::from_le_bytes(input.get(8..12)?.try_into().ok()?) as usize;
+        input = input.get(12 + length..)?;
+    }
+    let palette_size = input.read_u16::<LittleEndian>().ok()? as usize;
+    input = input.get(palette_size * 3..)?;
+
+    // Width has the LZO flag
+    let width = input.read_u16::<LittleEndian>().ok()? & 0x7FFF;
+    let height = input.read_u16::<LittleEndian>().ok()?;
+    (width != 0 && height != 0).then(|| UVec2::new(width as u32, height as u32))
+}
+
 #[derive(Debug)]
 struct Paa {
-    type_: PaaType,
+    format: PaaFormat,
     tags: Vec<PaaTag>,
     palette: Vec<u32>,
     mipmaps: Vec<PaaMipmap>,
 }
 
 impl Paa {
-    fn read_from<R: Read + Seek>(input: &mut R) -> Result<Paa> {
-        let type_ = PaaType::read_from(input)?;
+    fn read_from<R: Read + Seek>(input: &mut R) -> Result<Paa, PaaError> {
+        let format = PaaFormat::read_from(input)?;
 
+        // Tags are prefixed with GGAT, anything else starts the palette
         let mut tags = Vec::new();
-        let mut position = input.stream_position()?;
-        while let Ok(tag) = PaaTag::read_from(input) {
-            tags.push(tag);
-            position = input.stream_position()?;
+        loop {
+            let offset = position(input);
+            let mut signature = [0; 4];
+            input
+                .read_exact(&mut signature)
+                .map_err(truncated(None, offset))?;
+            if &signature != b"GGAT" {
+                input
+                    .seek(SeekFrom::Start(offset))
+                    .map_err(truncated(None, offset))?;
+                break;
+            }
+            tags.push(PaaTag::read_from(input, offset)?);
         }
-        input.seek(SeekFrom::Start(position))?;
 
-        let palette_size = input.read_u16::<LittleEndian>()?;
-        let mut palette = Vec::new();
+        let offset = position(input);
+        let palette_size = input
+            .read_u16::<LittleEndian>()
+            .map_err(truncated(None, offset))?;
+        let mut palette = Vec::with_capacity(palette_size as usize);
         for _ in 0..palette_size {
-            palette.push(input.read_u24::<LittleEndian>()?);
+            palette.push(
+                input
+                    .read_u24::<LittleEndian>()
+                    .map_err(truncated(None, offset))?,
+            );
         }
 
+        // Mipmaps are terminated by a mipmap of size zero
         let mut mipmaps = Vec::new();
-        while let Ok(mipmap) = PaaMipmap::read_from(input, &type_) {
-            mipmaps.push(mipmap);
+        loop {
+            let offset = position(input);
+            let index = mipmaps.len();
+            let width = input
+                .read_u16::<LittleEndian>()
+                .map_err(truncated(Some(index), offset))?;
+            let height = input
+                .read_u16::<LittleEndian>()
+                .map_err(truncated(Some(index), offset))?;
+            if width == 0 && height == 0 {
+                break;
+            }
+            if index >= MAXIMUM_MIPMAP_COUNT {
+                return Err(PaaError::TooManyMipmaps { offset });
+            }
+            mipmaps.push(PaaMipmap::read_from(
+                input, format, width, height, index, offset,
+            )?);
+        }
+        if mipmaps.is_empty() {
+            return Err(PaaError::NoMipmaps);
         }
 
         Ok(Self {
-            type_,
+            format,
             tags,
             palette,
             mipmaps,
@@ -105,32 +422,166 @@ impl Paa {
     }
 }
 
-#[derive(Debug)]
-enum PaaType {
+fn truncated<E>(mipmap: Option<usize>, offset: u64) -> impl FnOnce(E) -> PaaError {
+    move |_| PaaError::UnexpectedEndOfFile { mipmap, offset }
+}
+
+/// Pixel format of a PAA, as stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaaFormat {
+    Index8,
+    Ai88,
+    Rgb565,
+    Argb1555,
+    Argb4444,
+    Argb8888,
     Dxt1,
+    Dxt2,
+    Dxt3,
+    Dxt4,
     Dxt5,
 }
 
-impl PaaType {
-    fn read_from<R: Read>(input: &mut R) -> Result<PaaType> {
-        Ok(match input.read_u16::<LittleEndian>()? {
-            0xFF01 => Self::Dxt1,
-            0xFF05 => Self::Dxt5,
-            _ => todo!(),
+impl PaaFormat {
+    /// Converts from the format numbering used by texHeaders.bin.
+    pub fn from_index(index: u32) -> Option<Self> {
+        Some(match index {
+            0 => Self::Index8,
+            1 => Self::Ai88,
+            2 => Self::Rgb565,
+            3 => Self::Argb1555,
+            4 => Self::Argb4444,
+            5 => Self::Argb8888,
+            6 => Self::Dxt1,
+            7 => Self::Dxt2,
+            8 => Self::Dxt3,
+            9 => Self::Dxt4,
+            10 => Self::Dxt5,
+            _ => return None,
         })
     }
+}
 
-    fn size(&self, width: usize, height: usize) -> usize {
+impl PaaFormat {
+    fn read_from<R: Read>(input: &mut R) -> Result<Self, PaaError> {
+        Ok(
+            match input
+                .read_u16::<LittleEndian>()
+                .map_err(truncated(None, 0))?
+            {
+                0xFF01 => Self::Dxt1,
+                0xFF02 => Self::Dxt2,
+                0xFF03 => Self::Dxt3,
+                0xFF04 => Self::Dxt4,
+                0xFF05 => Self::Dxt5,
+                0x4444 => Self::Argb4444,
+                0x1555 => Self::Argb1555,
+                0x8888 => Self::Argb8888,
+                0x8080 => Self::Ai88,
+                type_ => return Err(PaaError::UnknownType(type_)),
+            },
+        )
+    }
+
+    fn is_dxt(&self) -> bool {
+        matches!(
+            self,
+            Self::Dxt1 | Self::Dxt2 | Self::Dxt3 | Self::Dxt4 | Self::Dxt5
+        )
+    }
+
+    /// Returns the width and height of a block in pixels, and its size in bytes, uncompressed
+    /// formats have blocks of a single pixel.
+    fn block(&self) -> (usize, usize, usize) {
         match self {
-            Self::Dxt1 => next_multiple_of(width, 4) * next_multiple_of(height, 4) / 2,
-            Self::Dxt5 => next_multiple_of(width, 4) * next_multiple_of(height, 4),
+            Self::Index8 => (1, 1, 1),
+            Self::Ai88 | Self::Rgb565 | Self::Argb1555 | Self::Argb4444 => (1, 1, 2),
+            Self::Argb8888 => (1, 1, 4),
+            Self::Dxt1 => (4, 4, 8),
+            Self::Dxt2 | Self::Dxt3 | Self::Dxt4 | Self::Dxt5 => (4, 4, 16),
         }
     }
 
+    /// Returns the size in bytes of a mipmap as stored, after decompression.
+    fn size(&self, width: usize, height: usize) -> usize {
+        data_size(self.block(), width, height)
+    }
+
+    /// Returns the format of the image, uncompressed formats are converted to RGBA.
     fn texture_format(&self) -> TextureFormat {
         match self {
             Self::Dxt1 => TextureFormat::Bc1RgbaUnorm,
-            Self::Dxt5 => TextureFormat::Bc3RgbaUnorm,
+            Self::Dxt2 | Self::Dxt3 => TextureFormat::Bc2RgbaUnorm,
+            Self::Dxt4 | Self::Dxt5 => TextureFormat::Bc3RgbaUnorm,
+            _ => TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    /// Converts the data of a mipmap to the texture format.
+    fn decode(&self, data: Vec<u8>, palette: &[u32]) -> Vec<u8> {
+        let expand = |value: u16, bits: u32| {
+            let maximum = (1 << bits) - 1;
+            ((value & maximum) as u32 * 255 / maximum as u32) as u8
+        };
+        let pixels = |data: &[u8]| {
+            data.chunks_exact(2)
+                .map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]]))
+                .collect::<Vec<_>>()
+        };
+        match self {
+            Self::Index8 => data
+                .iter()
+                .flat_map(|&index| {
+                    let [blue, green, red, _] = palette
+                        .get(index as usize)
+                        .copied()
+                        .unwrap_or_default()
+                        .to_le_bytes();
+                    [red, green, blue, 0xFF]
+                })
+                .collect(),
+            Self::Ai88 => data
+                .chunks_exact(2)
+                .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+                .collect(),
+            Self::Rgb565 => pixels(&data)
+                .into_iter()
+                .flat_map(|pixel| {
+                    [
+                        expand(pixel >> 11, 5),
+                        expand(pixel >> 5, 6),
+                        expand(pixel, 5),
+                        0xFF,
+                    ]
+                })
+                .collect(),
+            Self::Argb1555 => pixels(&data)
+                .into_iter()
+                .flat_map(|pixel| {
+                    [
+                        expand(pixel >> 10, 5),
+                        expand(pixel >> 5, 5),
+                        expand(pixel, 5),
+                        expand(pixel >> 15, 1),
+                    ]
+                })
+                .collect(),
+            Self::Argb4444 => pixels(&data)
+                .into_iter()
+                .flat_map(|pixel| {
+                    [
+                        expand(pixel >> 8, 4),
+                        expand(pixel >> 4, 4),
+                        expand(pixel, 4),
+                        expand(pixel >> 12, 4),
+                    ]
+                })
+                .collect(),
+            Self::Argb8888 => data
+                .chunks_exact(4)
+                .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+                .collect(),
+            Self::Dxt1 | Self::Dxt2 | Self::Dxt3 | Self::Dxt4 | Self::Dxt5 => data,
         }
     }
 }
@@ -139,47 +590,81 @@ impl PaaType {
 enum PaaTag {
     AverageColor(u32),
     MaximumColor(u32),
+    Flags(u32),
     Swizzle(u32),
+    Procedural(String),
     Offsets([u32; 16]),
 }
 
 impl PaaTag {
-    fn read_from<R: Read>(input: &mut R) -> Result<PaaTag> {
-        let id: [u8; 8] = input.read_u64::<LittleEndian>()?.to_be_bytes();
-        let length = input.read_u32::<LittleEndian>()?;
-
-        Ok(match &id {
-            b"AVGCTAGG" => {
-                if length != 4 {
-                    bail!(PaaError::InvalidTag)
-                }
-
-                PaaTag::AverageColor(input.read_u32::<LittleEndian>()?)
-            }
-            b"MAXCTAGG" => {
-                if length != 4 {
-                    bail!(PaaError::InvalidTag)
-                }
-
-                PaaTag::MaximumColor(input.read_u32::<LittleEndian>()?)
-            }
-            b"SWIZTAGG" => {
-                if length != 4 {
-                    bail!(PaaError::InvalidTag)
-                }
+    /// Returns the name and a readable value.
+    fn describe(&self) -> (String, String) {
+        let (name, value) = match self {
+            Self::AverageColor(color) => ("AVGCTAGG", format!("#{color:08x}")),
+            Self::MaximumColor(color) => ("MAXCTAGG", format!("#{color:08x}")),
+            Self::Flags(flags) => ("FLAGTAGG", format!("{flags:#x}")),
+            Self::Swizzle(swizzle) => ("SWIZTAGG", format!("{swizzle:#010x}")),
+            Self::Procedural(code) => ("PROCTAGG", code.clone()),
+            Self::Offsets(offsets) => (
+                "OFFSTAGG",
+                offsets
+                    .iter()
+                    .take_while(|&&offset| offset != 0)
+                    .map(|offset| offset.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        };
+        (name.to_string(), value)
+    }
 
-                PaaTag::Swizzle(input.read_u32::<LittleEndian>()?)
-            }
-            b"OFFSTAGG" => {
-                if length != 64 {
-                    bail!(PaaError::InvalidTag)
-                }
+    /// Reads a tag after its GGAT signature, the offset is the one of the signature.
+    fn read_from<R: Read>(input: &mut R, offset: u64) -> Result<PaaTag, PaaError> {
+        let mut name = [0; 4];
+        input
+            .read_exact(&mut name)
+            .map_err(truncated(None, offset))?;
+        let length = input
+            .read_u32::<LittleEndian>()
+            .map_err(truncated(None, offset))?;
+
+        // Names are stored reversed, e.g. GGATCGVA
+        name.reverse();
+        let name = format!("{}TAGG", String::from_utf8_lossy(&name));
+        let expected_length = match name.as_str() {
+            "AVGCTAGG" | "MAXCTAGG" | "FLAGTAGG" | "SWIZTAGG" => Some(4),
+            "OFFSTAGG" => Some(64),
+            "PROCTAGG" => None,
+            _ => return Err(PaaError::UnknownTag { name, offset }),
+        };
+        if expected_length.is_some_and(|expected_length| length != expected_length) {
+            return Err(PaaError::InvalidTagLength {
+                name,
+                length,
+                offset,
+            });
+        }
 
-                PaaTag::Offsets(core::array::from_fn(|_| {
-                    input.read_u32::<LittleEndian>().unwrap()
-                }))
-            }
-            _ => bail!(PaaError::UnknownTag(std::str::from_utf8(&id)?.to_string())),
+        let mut data = Vec::new();
+        input
+            .take(length as u64)
+            .read_to_end(&mut data)
+            .map_err(truncated(None, offset))?;
+        if data.len() != length as usize {
+            return Err(PaaError::UnexpectedEndOfFile {
+                mipmap: None,
+                offset,
+            });
+        }
+        let value = |index: usize| u32::from_le_bytes(data[index * 4..][..4].try_into().unwrap());
+
+        Ok(match name.as_str() {
+            "AVGCTAGG" => PaaTag::AverageColor(value(0)),
+            "MAXCTAGG" => PaaTag::MaximumColor(value(0)),
+            "FLAGTAGG" => PaaTag::Flags(value(0)),
+            "SWIZTAGG" => PaaTag::Swizzle(value(0)),
+            "OFFSTAGG" => PaaTag::Offsets(core::array::from_fn(value)),
+            _ => PaaTag::Procedural(String::from_utf8_lossy(&data).into_owned()),
         })
     }
 }
@@ -192,26 +677,63 @@ struct PaaMipmap {
 }
 
 impl PaaMipmap {
-    fn read_from<R: Read>(input: &mut R, type_: &PaaType) -> Result<Self> {
-        let mut width = input.read_u16::<LittleEndian>()?;
-        let mut height = input.read_u16::<LittleEndian>()?;
-        let size = input.read_u24::<LittleEndian>()?;
-        let mut data = Vec::new();
-        data.resize(size as usize, 0);
-        input.read_exact(&mut data)?;
-
-        let data = /*if width == 1234 && height == 8765 {
-            width = input.read_u16::<LittleEndian>()?;
-            height = input.read_u16::<LittleEndian>()?;
+    /// Reads a mipmap after its size, the offset is the one of the size.
+    fn read_from<R: Read>(
+        input: &mut R,
+        format: PaaFormat,
+        width: u16,
+        height: u16,
+        index: usize,
+        offset: u64,
+    ) -> Result<Self, PaaError> {
+        // Highest bit of the width flags LZO compression of DXT mipmaps
+        let compressed = width & 0x8000 != 0;
+        let width = width & 0x7FFF;
+        if width == 0 || height == 0 {
+            return Err(PaaError::InvalidMipmapSize {
+                mipmap: index,
+                width,
+                height,
+                offset,
+            });
+        }
 
-            todo!()
-        } else */if width & 0x8000 != 0 {
-            width &= 0x7FFF;
+        let size = input
+            .read_u24::<LittleEndian>()
+            .map_err(truncated(Some(index), offset))?;
+        let mut data = Vec::new();
+        input
+            .take(size as u64)
+            .read_to_end(&mut data)
+            .map_err(truncated(Some(index), offset))?;
+        if data.len() != size as usize {
+            return Err(PaaError::UnexpectedEndOfFile {
+                mipmap: Some(index),
+                offset,
+            });
+        }
 
-            minilzo::decompress(&data, type_.size(width as usize, height as usize))?
+        // Uncompressed formats are compressed with LZSS when smaller than expected
+        let expected = format.size(width as usize, height as usize);
+        let data = if compressed {
+            minilzo::decompress(&data, expected).map_err(anyhow::Error::from)
+        } else if !format.is_dxt() && data.len() < expected {
+            lzss_decompress(&mut data.as_slice(), expected)
         } else {
-            data
-        };
+            Ok(data)
+        }
+        .map_err(|_| PaaError::InvalidCompressedData {
+            mipmap: index,
+            offset,
+        })?;
+        if data.len() != expected {
+            return Err(PaaError::DataSizeMismatch {
+                mipmap: index,
+                expected,
+                actual: data.len(),
+                offset,
+            });
+        }
 
         Ok(Self {
             width,
@@ -221,7 +743,126 @@ impl PaaMipmap {
     }
 }
 
+/// Returns the size in bytes of an image of whole blocks, blocks are given as width and height in
+/// pixels and size in bytes.
+fn data_size(block: (usize, usize, usize), width: usize, height: usize) -> usize {
+    let (block_width, block_height, block_size) = block;
+    next_multiple_of(width, block_width) / block_width
+        * (next_multiple_of(height, block_height) / block_height)
+        * block_size
+}
+
+/// Rounds up to a multiple of a power of two.
 #[inline]
-fn next_multiple_of(value: usize, rhs: usize) -> usize {
-    (value + (rhs - 1)) & !rhs
+pub(crate) fn next_multiple_of(value: usize, rhs: usize) -> usize {
+    (value + (rhs - 1)) & !(rhs - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_multiple() {
+        assert_eq!(next_multiple_of(0, 4), 0);
+        assert_eq!(next_multiple_of(1, 4), 4);
+        assert_eq!(next_multiple_of(4, 4), 4);
+        assert_eq!(next_multiple_of(5, 4), 8);
+        assert_eq!(next_multiple_of(7, 1), 7);
+        assert_eq!(next_multiple_of(9, 8), 16);
+    }
+
+    #[test]
+    fn dxt_sizes() {
+        // Mipmaps smaller than a block occupy a whole block
+        for (width, height, blocks) in
+            [(1, 1, 1), (2, 2, 1), (4, 4, 1), (6, 2, 2), (256, 128, 2048)]
+        {
+            assert_eq!(PaaFormat::Dxt1.size(width, height), blocks * 8);
+            for format in [
+                PaaFormat::Dxt2,
+                PaaFormat::Dxt3,
+                PaaFormat::Dxt4,
+                PaaFormat::Dxt5,
+            ] {
+                assert_eq!(format.size(width, height), blocks * 16);
+            }
+        }
+    }
+
+    #[test]
+    fn uncompressed_sizes() {
+        for (width, height) in [(1, 1), (2, 2), (3, 5), (256, 128)] {
+            let pixels = width * height;
+            assert_eq!(PaaFormat::Index8.size(width, height), pixels);
+            for format in [
+                PaaFormat::Ai88,
+                PaaFormat::Rgb565,
+                PaaFormat::Argb1555,
+                PaaFormat::Argb4444,
+            ] {
+                assert_eq!(format.size(width, height), pixels * 2);
+            }
+            assert_eq!(PaaFormat::Argb8888.size(width, height), pixels * 4);
+        }
+    }
+
+    #[test]
+    fn decoded_sizes() {
+        // Uncompressed formats are converted to 4 bytes per pixel
+        for format in [
+            PaaFormat::Index8,
+            PaaFormat::Ai88,
+            PaaFormat::Rgb565,
+            PaaFormat::Argb1555,
+            PaaFormat::Argb4444,
+            PaaFormat::Argb8888,
+        ] {
+            let data = vec![0; format.size(3, 2)];
+            assert_eq!(format.decode(data, &[]).len(), 3 * 2 * 4);
+        }
+    }
+
+    #[test]
+    fn mipmap_count() {
+        let paa = |mipmap_count| {
+            let mut data = 0x8888u16.to_le_bytes().to_vec();
+            data.extend_from_slice(&0u16.to_le_bytes());
+            for _ in 0..mipmap_count {
+                data.extend_from_slice(&[1, 0, 1, 0, 4, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+            }
+            data.extend_from_slice(&[0; 4]);
+            Paa::read_from(&mut Cursor::new(data))
+        };
+        assert_eq!(paa(16).unwrap().mipmaps.len(), 16);
+        assert!(matches!(
+            paa(17),
+            Err(PaaError::TooManyMipmaps { offset: 180 })
+        ));
+    }
+
+    #[test]
+    fn size_from_header() {
+        let mut data = 0xFF01u16.to_le_bytes().to_vec();
+        data.extend_from_slice(b"GGATCGVA");
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&[0x80, 0x80, 0x80, 0xFF]);
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0]);
+        // LZO compressed, the data is not read
+        data.extend_from_slice(&(256u16 | 0x8000).to_le_bytes());
+        data.extend_from_slice(&128u16.to_le_bytes());
+        assert_eq!(paa_size(&data), Some(UVec2::new(256, 128)));
+
+        assert_eq!(paa_size(&data[..data.len() - 1]), None);
+    }
+
+    #[test]
+    fn decode_argb4444() {
+        let data = 0xF84Cu16.to_le_bytes().to_vec();
+        assert_eq!(
+            PaaFormat::Argb4444.decode(data, &[]),
+            [0x88, 0x44, 0xCC, 0xFF]
+        );
+    }
 }
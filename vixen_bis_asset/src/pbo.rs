@@ -0,0 +1,278 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use anyhow::{bail, Result};
+use byteorder::{LittleEndian, ReadBytesExt};
+use thiserror::Error;
+
+use crate::{compression::lzss_decompress, p3d::read_asciiz, texheaders::TexHeaders};
+
+const PACKING_METHOD_UNCOMPRESSED: u32 = 0x00000000;
+const PACKING_METHOD_COMPRESSED: u32 = 0x43707273;
+const PACKING_METHOD_VERSION: u32 = 0x56657273;
+
+#[derive(Error, Debug)]
+enum PboError {
+    #[error("unknown packing method: {0:#010x}")]
+    UnknownPackingMethod(u32),
+    #[error("entry out of bounds: {0}")]
+    EntryOutOfBounds(String),
+}
+
+/// Header of a PBO, entry data is read on demand.
+#[derive(Clone, Debug, Default)]
+pub struct Pbo {
+    /// Key-value pairs of the version entry, e.g. `prefix`.
+    pub extensions: Vec<(String, String)>,
+    pub entries: Vec<PboEntry>,
+    /// End of the entry data, followed by the checksum.
+    pub data_end: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct PboEntry {
+    /// Path inside of the PBO, separated by backslashes.
+    pub name: String,
+    pub compressed: bool,
+    pub original_size: u32,
+    pub timestamp: u32,
+    pub data_size: u32,
+    /// Absolute offset of the data.
+    pub offset: u64,
+}
+
+impl Pbo {
+    pub fn read_from<R: Read + Seek>(input: &mut R) -> Result<Self> {
+        let mut pbo = Self::default();
+        loop {
+            let name = read_asciiz(input)?;
+            let packing_method = input.read_u32::<LittleEndian>()?;
+            let original_size = input.read_u32::<LittleEndian>()?;
+            let _reserved = input.read_u32::<LittleEndian>()?;
+            let timestamp = input.read_u32::<LittleEndian>()?;
+            let data_size = input.read_u32::<LittleEndian>()?;
+
+            match packing_method {
+                // Version entry, followed by extensions
+                PACKING_METHOD_VERSION if name.is_empty() => loop {
+                    let key = read_asciiz(input)?;
+                    if key.is_empty() {
+                        break;
+                    }
+                    let value = read_asciiz(input)?;
+                    pbo.extensions.push((key, value));
+                },
+                // Empty entry terminates the header
+                PACKING_METHOD_UNCOMPRESSED if name.is_empty() => break,
+                PACKING_METHOD_UNCOMPRESSED | PACKING_METHOD_COMPRESSED => {
+                    pbo.entries.push(PboEntry {
+                        name,
+                        compressed: packing_method == PACKING_METHOD_COMPRESSED,
+                        original_size,
+                        timestamp,
+                        data_size,
+                        offset: 0,
+                    })
+                }
+                _ => bail!(PboError::UnknownPackingMethod(packing_method)),
+            }
+        }
+
+        // Data follows the header in the same order
+        let mut offset = input.stream_position()?;
+        for entry in &mut pbo.entries {
+            entry.offset = offset;
+            offset += entry.data_size as u64;
+        }
+        pbo.data_end = offset;
+
+        let length = input.seek(SeekFrom::End(0))?;
+        // Entries have to end inside of the file, as their data is allocated up front
+        if let Some(entry) = pbo.entries.iter().find(|entry| {
+            entry
+                .offset
+                .checked_add(entry.data_size as u64)
+                .is_none_or(|end| end > length)
+        }) {
+            bail!(PboError::EntryOutOfBounds(entry.name.clone()))
+        }
+
+        Ok(pbo)
+    }
+
+    /// Returns the value of an extension, keys are case-insensitive.
+    pub fn extension(&self, key: &str) -> Option<&str> {
+        self.extensions
+            .iter()
+            .find(|(extension_key, _)| extension_key.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the path prefix of all entries, with forward slashes.
+    pub fn prefix(&self) -> String {
+        self.extension("prefix")
+            .unwrap_or_default()
+            .trim_matches('\\')
+            .replace('\\', "/")
+    }
+
+    /// Returns an entry by its path, which is case-insensitive and may use forward slashes.
+    pub fn entry(&self, name: &str) -> Option<&PboEntry> {
+        let name = name.replace('/', "\\");
+        self.entries
+            .iter()
+            .find(|entry| entry.name.eq_ignore_ascii_case(&name))
+    }
+
+    /// Reads the data of an entry as stored.
+    pub fn read_raw<R: Read + Seek>(&self, input: &mut R, entry: &PboEntry) -> Result<Vec<u8>> {
+        input.seek(SeekFrom::Start(entry.offset))?;
+        let mut data = vec![0; entry.data_size as usize];
+        input.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// Reads the data of an entry, decompressing it if needed.
+    pub fn read<R: Read + Seek>(&self, input: &mut R, entry: &PboEntry) -> Result<Vec<u8>> {
+        let data = self.read_raw(input, entry)?;
+        if entry.compressed && entry.original_size != entry.data_size {
+            lzss_decompress(&mut data.as_slice(), entry.original_size as usize)
+        } else {
+            Ok(data)
+        }
+    }
+
+    /// Reads the texture headers of a binarized addon, if there are any.
+    pub fn read_tex_headers<R: Read + Seek>(&self, input: &mut R) -> Result<Option<TexHeaders>> {
+        let Some(entry) = self.entry("texHeaders.bin") else {
+            return Ok(None);
+        };
+        let data = self.read(input, entry)?;
+        Ok(Some(TexHeaders::read_from(
+            &mut data.as_slice(),
+            self.extension("prefix").unwrap_or_default(),
+        )?))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Cursor;
+
+    use sha1::{Digest, Sha1};
+
+    use super::*;
+    use crate::compression::lzss_compress;
+
+    fn write_entry(output: &mut Vec<u8>, name: &str, header: [u32; 5]) {
+        output.extend_from_slice(name.as_bytes());
+        output.push(0);
+        for value in header {
+            output.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    /// Writes a PBO of entries given as name, whether to compress and data.
+    pub(crate) fn write_pbo(prefix: &str, entries: &[(&str, bool, &[u8])]) -> Vec<u8> {
+        let mut output = Vec::new();
+        write_entry(&mut output, "", [PACKING_METHOD_VERSION, 0, 0, 0, 0]);
+        if !prefix.is_empty() {
+            output.extend_from_slice(b"prefix\0");
+            output.extend_from_slice(prefix.as_bytes());
+            output.push(0);
+        }
+        output.push(0);
+
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|&(name, compressed, data)| {
+                let stored = if compressed {
+                    lzss_compress(data)
+                } else {
+                    data.to_vec()
+                };
+                let packing_method = if compressed {
+                    PACKING_METHOD_COMPRESSED
+                } else {
+                    PACKING_METHOD_UNCOMPRESSED
+                };
+                (name, packing_method, data.len() as u32, stored)
+            })
+            .collect();
+        for (name, packing_method, original_size, stored) in &entries {
+            let header = [*packing_method, *original_size, 0, 0, stored.len() as u32];
+            write_entry(&mut output, name, header);
+        }
+        write_entry(&mut output, "", [0; 5]);
+        for (_, _, _, stored) in &entries {
+            output.extend_from_slice(stored);
+        }
+
+        let checksum = Sha1::digest(&output);
+        output.push(0);
+        output.extend_from_slice(&checksum);
+        output
+    }
+
+    #[test]
+    fn read() {
+        let text = b"class CfgPatches {};";
+        let compressible = b"vixen".repeat(200);
+        let data = write_pbo(
+            "vixen\\test",
+            &[
+                ("config.cpp", false, text),
+                ("Data\\Big.txt", true, &compressible),
+            ],
+        );
+        let mut input = Cursor::new(&data);
+        let pbo = Pbo::read_from(&mut input).unwrap();
+
+        assert_eq!(pbo.extension("PREFIX"), Some("vixen\\test"));
+        assert_eq!(pbo.prefix(), "vixen/test");
+        assert_eq!(pbo.entries.len(), 2);
+        assert_eq!(pbo.data_end, data.len() as u64 - 21);
+        assert!(pbo.entry("texHeaders.bin").is_none());
+
+        let entry = pbo.entry("config.cpp").unwrap();
+        assert!(!entry.compressed);
+        assert_eq!(pbo.read(&mut input, entry).unwrap(), text);
+
+        let entry = pbo.entry("data/big.TXT").unwrap();
+        assert!(entry.compressed);
+        assert_eq!(entry.original_size, 1000);
+        assert!(entry.data_size < entry.original_size);
+        assert_ne!(pbo.read_raw(&mut input, entry).unwrap(), compressible);
+        assert_eq!(pbo.read(&mut input, entry).unwrap(), compressible);
+    }
+
+    #[test]
+    fn entry_out_of_bounds() {
+        let data = write_pbo("", &[("config.cpp", false, b"class CfgPatches {};")]);
+        let data_end = data.len() - 21;
+
+        // The checksum is optional, but entry data has to be complete
+        let pbo = Pbo::read_from(&mut Cursor::new(&data[..data_end])).unwrap();
+        assert_eq!(pbo.data_end, data_end as u64);
+
+        let error = Pbo::read_from(&mut Cursor::new(&data[..data_end - 1])).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(PboError::EntryOutOfBounds(name)) if name == "config.cpp"
+        ));
+
+        // Sizes close to the maximum are rejected without overflowing
+        let mut data = data;
+        let size = data
+            .windows(11)
+            .position(|name| name == b"config.cpp\0")
+            .unwrap()
+            + 11
+            + 16;
+        data[size..size + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let error = Pbo::read_from(&mut Cursor::new(&data)).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(PboError::EntryOutOfBounds(_))
+        ));
+    }
+}
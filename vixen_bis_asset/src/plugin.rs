@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use crate::{
+    config::{ConfigClass, ConfigLoader},
+    fxy::FxyPlugin,
+    lip::LipPlugin,
+    p3d::{P3dPlugin, P3dSettings},
+    paa::{PaaArrayLoader, PaaLoader},
+    rvmat::{RvmatMaterial, RvmatMaterialLoader},
+    sqm::SqmPlugin,
+    terrain::TerrainPlugin,
+    texheaders::TexHeaders,
+    wss::WssLoader,
+};
+
+/// Registers all Bohemia loaders and the asset types they produce.
+///
+/// PBOs are mounted by [`PboAssetIoPlugin`](crate::PboAssetIoPlugin), which has to be added before
+/// the `AssetPlugin`, otherwise [`TexHeaders`] stays empty.
+#[derive(Default)]
+pub struct BisAssetPlugin {
+    pub p3d: P3dSettings,
+    /// Game config, used to resolve models of mission objects.
+    pub config: Option<Arc<ConfigClass>>,
+}
+
+impl Plugin for BisAssetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(P3dPlugin {
+            settings: self.p3d.clone(),
+        })
+        .add_plugin(FxyPlugin)
+        .add_plugin(LipPlugin)
+        .add_plugin(SqmPlugin {
+            config: self.config.clone(),
+        })
+        .add_plugin(TerrainPlugin)
+        .add_asset::<ConfigClass>()
+        .add_asset::<RvmatMaterial>()
+        .init_asset_loader::<ConfigLoader>()
+        .init_asset_loader::<PaaLoader>()
+        .init_asset_loader::<PaaArrayLoader>()
+        .init_asset_loader::<RvmatMaterialLoader>()
+        .init_asset_loader::<WssLoader>()
+        .init_resource::<TexHeaders>();
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::Result;
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ConfigClass, ConfigCursor, ConfigValue};
+
+/// Loader of material descriptions serialized by the asset conversion, with the `rvmat.json`
+/// extension.
+#[derive(Default)]
+pub struct RvmatMaterialLoader;
+
+impl AssetLoader for RvmatMaterialLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let material: RvmatMaterial = serde_json::from_slice(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(material));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rvmat.json"]
+    }
+}
+
+/// Description of an RVMAT, colors are RGBA multipliers as given in the file.
+#[derive(TypeUuid, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[uuid = "2f8b6d13-a4c7-4e59-b1d0-6c3e9a5f7b82"]
+pub struct RvmatMaterial {
+    pub ambient: [f32; 4],
+    pub diffuse: [f32; 4],
+    pub forced_diffuse: [f32; 4],
+    pub emissive: [f32; 4],
+    pub specular: [f32; 4],
+    pub specular_power: f32,
+    pub pixel_shader: String,
+    pub vertex_shader: String,
+    /// Texture stages in order of appearance, e.g. the normal map in `Stage1`.
+    pub stages: Vec<RvmatStage>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RvmatStage {
+    pub name: String,
+    /// Path relative to the root, or a procedural texture.
+    pub texture: String,
+    pub uv_source: String,
+}
+
+impl RvmatMaterial {
+    pub fn from_config(rvmat: &ConfigClass) -> Self {
+        let rvmat = ConfigCursor::new(rvmat);
+        let string = |cursor: &ConfigCursor, name: &str| {
+            cursor
+                .value(name)
+                .and_then(ConfigValue::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let color = |name: &str, default: [f32; 4]| {
+            let Some(values) = rvmat.value(name).and_then(ConfigValue::as_array) else {
+                return default;
+            };
+            let mut color = default;
+            for (component, value) in color.iter_mut().zip(values) {
+                *component = value.as_f32().unwrap_or(*component);
+            }
+            color
+        };
+
+        Self {
+            ambient: color("ambient", [1.0; 4]),
+            diffuse: color("diffuse", [1.0; 4]),
+            forced_diffuse: color("forcedDiffuse", [0.0; 4]),
+            // Misspelled in RVMATs
+            emissive: color("emmisive", [0.0; 4]),
+            specular: color("specular", [0.0; 4]),
+            specular_power: rvmat
+                .value("specularPower")
+                .and_then(ConfigValue::as_f32)
+                .unwrap_or(1.0),
+            pixel_shader: string(&rvmat, "PixelShaderID"),
+            vertex_shader: string(&rvmat, "VertexShaderID"),
+            stages: rvmat
+                .classes()
+                .into_iter()
+                .filter(|stage| stage.name().to_ascii_lowercase().starts_with("stage"))
+                .map(|stage| {
+                    let texture = string(&stage, "texture");
+                    RvmatStage {
+                        name: stage.name().to_string(),
+                        texture: if texture.starts_with('#') {
+                            texture
+                        } else {
+                            texture
+                                .trim_start_matches('\\')
+                                .replace('\\', "/")
+                                .to_ascii_lowercase()
+                        },
+                        uv_source: string(&stage, "uvSource"),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Creates a standard material with the colors and normal map, the base color texture is
+    /// defined by the faces of the model.
+    pub fn standard_material(&self, asset_server: &AssetServer) -> StandardMaterial {
+        // Blinn-Phong exponent to roughness
+        let roughness = (2.0 / (self.specular_power.max(0.0) + 2.0)).sqrt();
+        let [red, green, blue, alpha] = self.diffuse;
+        let [emissive_red, emissive_green, emissive_blue, _] = self.emissive;
+        StandardMaterial {
+            base_color: Color::rgba(red, green, blue, alpha),
+            emissive: Color::rgb(emissive_red, emissive_green, emissive_blue),
+            perceptual_roughness: roughness.sqrt(),
+            normal_map_texture: self
+                .stages
+                .iter()
+                .find(|stage| stage.texture.contains("_nohq.") || stage.texture.contains("_no."))
+                .map(|stage| asset_server.load(stage.texture.as_str())),
+            ..default()
+        }
+    }
+}
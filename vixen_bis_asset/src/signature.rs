@@ -0,0 +1,505 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use byteorder::{LittleEndian, ReadBytesExt};
+use num_bigint::BigUint;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+
+use crate::{
+    p3d::read_asciiz,
+    pbo::{Pbo, PboEntry},
+};
+
+const PUBLIC_KEY_BLOB: u8 = 0x06;
+const CALG_RSA_SIGN: u32 = 0x00002400;
+
+/// Upper bound of key and signature lengths in bytes, those of 4096-bit RSA keys.
+const MAXIMUM_KEY_LENGTH: usize = 512;
+
+/// DER encoded `DigestInfo` prefix of SHA-1, as used by PKCS #1 v1.5.
+const SHA1_DIGEST_INFO: [u8; 15] = [
+    0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2B, 0x0E, 0x03, 0x02, 0x1A, 0x05, 0x00, 0x04, 0x14,
+];
+
+/// Extensions which are not part of the file hash of version 2 signatures.
+const V2_EXCLUDED_EXTENSIONS: [&str; 13] = [
+    "paa", "jpg", "p3d", "tga", "rvmat", "lip", "ogg", "wss", "png", "rtm", "pac", "fxy", "wrp",
+];
+
+/// Extensions which are the only part of the file hash of version 3 signatures.
+const V3_INCLUDED_EXTENSIONS: [&str; 10] = [
+    "sqf", "inc", "bikb", "ext", "fsm", "sqm", "hpp", "cfg", "sqs", "h",
+];
+
+#[derive(Error, Debug)]
+enum SignatureError {
+    #[error("invalid key")]
+    InvalidKey,
+    #[error("unknown signature version: {0}")]
+    UnknownVersion(u32),
+    #[error("signature authority {0} does not match key authority {1}")]
+    AuthorityMismatch(String, String),
+    #[error("signature key does not match")]
+    KeyMismatch,
+    #[error("hash {0} does not match")]
+    HashMismatch(usize),
+    #[error("missing signature")]
+    MissingSignature,
+    #[error("key or signature length {0} exceeds {MAXIMUM_KEY_LENGTH} bytes")]
+    KeyTooLong(usize),
+}
+
+/// Public key of an authority, as stored in .bikey files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BiPublicKey {
+    pub authority: String,
+    pub exponent: u32,
+    pub modulus: BigUint,
+    /// Length of the modulus in bytes.
+    pub length: usize,
+}
+
+impl BiPublicKey {
+    pub fn read_from<R: Read>(input: &mut R) -> Result<Self> {
+        let authority = read_asciiz(input)?;
+        let _blob_length = input.read_u32::<LittleEndian>()?;
+
+        // PUBLICKEYBLOB of the Windows CryptoAPI
+        let blob_type = input.read_u8()?;
+        let _blob_version = input.read_u8()?;
+        let _reserved = input.read_u16::<LittleEndian>()?;
+        let algorithm = input.read_u32::<LittleEndian>()?;
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if blob_type != PUBLIC_KEY_BLOB || algorithm != CALG_RSA_SIGN || &magic != b"RSA1" {
+            bail!(SignatureError::InvalidKey)
+        }
+
+        let bit_length = input.read_u32::<LittleEndian>()?;
+        let exponent = input.read_u32::<LittleEndian>()?;
+        let length = bit_length as usize / 8;
+        if length > MAXIMUM_KEY_LENGTH {
+            bail!(SignatureError::KeyTooLong(length))
+        }
+        let mut modulus = vec![0; length];
+        input.read_exact(&mut modulus)?;
+
+        Ok(Self {
+            authority,
+            exponent,
+            modulus: BigUint::from_bytes_le(&modulus),
+            length,
+        })
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BiSignatureVersion {
+    V2,
+    V3,
+}
+
+/// Signature of a PBO, as stored in .bisign files.
+#[derive(Clone, Debug)]
+pub struct BiSignature {
+    pub key: BiPublicKey,
+    pub version: BiSignatureVersion,
+    /// Signatures of the PBO checksum, of it with the name hash, and of the file hash with the
+    /// name hash.
+    pub signatures: [BigUint; 3],
+}
+
+impl BiSignature {
+    pub fn read_from<R: Read>(input: &mut R) -> Result<Self> {
+        let key = BiPublicKey::read_from(input)?;
+        let signature1 = read_signature(input)?;
+        let version = match input.read_u32::<LittleEndian>()? {
+            2 => BiSignatureVersion::V2,
+            3 => BiSignatureVersion::V3,
+            version => bail!(SignatureError::UnknownVersion(version)),
+        };
+        let signature2 = read_signature(input)?;
+        let signature3 = read_signature(input)?;
+
+        Ok(Self {
+            key,
+            version,
+            signatures: [signature1, signature2, signature3],
+        })
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Verifies a PBO against this signature and the public key.
+    pub fn verify<R: Read + Seek>(&self, key: &BiPublicKey, input: &mut R) -> Result<()> {
+        if !self.key.authority.eq_ignore_ascii_case(&key.authority) {
+            bail!(SignatureError::AuthorityMismatch(
+                self.key.authority.clone(),
+                key.authority.clone()
+            ))
+        }
+        if self.key != *key {
+            bail!(SignatureError::KeyMismatch)
+        }
+
+        let hashes = pbo_hashes(input, self.version)?;
+        for (index, (signature, hash)) in self.signatures.iter().zip(&hashes).enumerate() {
+            let exponent = BigUint::from(key.exponent);
+            if signature.modpow(&exponent, &key.modulus) != pad_hash(hash, key.length) {
+                bail!(SignatureError::HashMismatch(index + 1))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn read_signature<R: Read>(input: &mut R) -> Result<BigUint> {
+    let length = input.read_u32::<LittleEndian>()? as usize;
+    if length > MAXIMUM_KEY_LENGTH {
+        bail!(SignatureError::KeyTooLong(length))
+    }
+    let mut signature = vec![0; length];
+    input.read_exact(&mut signature)?;
+    Ok(BigUint::from_bytes_le(&signature))
+}
+
+/// Pads a hash with PKCS #1 v1.5 to the length of the key.
+fn pad_hash(hash: &[u8], length: usize) -> BigUint {
+    let mut data = vec![0x00, 0x01];
+    data.resize(
+        length.saturating_sub(1 + SHA1_DIGEST_INFO.len() + hash.len()),
+        0xFF,
+    );
+    data.push(0x00);
+    data.extend_from_slice(&SHA1_DIGEST_INFO);
+    data.extend_from_slice(hash);
+    BigUint::from_bytes_be(&data)
+}
+
+/// Computes the three hashes which are signed, like the game does.
+fn pbo_hashes<R: Read + Seek>(input: &mut R, version: BiSignatureVersion) -> Result<[Vec<u8>; 3]> {
+    let pbo = Pbo::read_from(input)?;
+
+    // Checksum of everything before the stored checksum
+    let mut checksum = Sha1::new();
+    input.seek(SeekFrom::Start(0))?;
+    std::io::copy(&mut (&mut *input).take(pbo.data_end), &mut checksum)?;
+    let checksum = checksum.finalize();
+
+    // Hash of all non-empty entry names, sorted and lowercase
+    let mut names: Vec<_> = pbo
+        .entries
+        .iter()
+        .filter(|entry| entry.data_size != 0)
+        .map(|entry| entry.name.to_ascii_lowercase())
+        .collect();
+    names.sort();
+    let mut name_hash = Sha1::new();
+    for name in &names {
+        name_hash.update(name.as_bytes());
+    }
+    let name_hash = name_hash.finalize();
+
+    // Hash of the data of entries, in order of the PBO
+    let mut file_hash = Sha1::new();
+    let mut empty = true;
+    for entry in pbo
+        .entries
+        .iter()
+        .filter(|entry| is_file_hashed(entry, version))
+    {
+        file_hash.update(pbo.read_raw(input, entry)?);
+        empty = false;
+    }
+    if empty {
+        file_hash.update(match version {
+            BiSignatureVersion::V2 => b"nothing",
+            BiSignatureVersion::V3 => b"gnihton",
+        });
+    }
+    let file_hash = file_hash.finalize();
+
+    let mut prefix = pbo.extension("prefix").unwrap_or_default().to_string();
+    if !prefix.is_empty() && !prefix.ends_with('\\') {
+        prefix.push('\\');
+    }
+
+    let mut hash2 = Sha1::new();
+    hash2.update(checksum);
+    hash2.update(name_hash);
+    hash2.update(prefix.as_bytes());
+
+    let mut hash3 = Sha1::new();
+    hash3.update(file_hash);
+    hash3.update(name_hash);
+    hash3.update(prefix.as_bytes());
+
+    Ok([
+        checksum.to_vec(),
+        hash2.finalize().to_vec(),
+        hash3.finalize().to_vec(),
+    ])
+}
+
+fn is_file_hashed(entry: &PboEntry, version: BiSignatureVersion) -> bool {
+    let extension = entry
+        .name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+    match version {
+        BiSignatureVersion::V2 => !V2_EXCLUDED_EXTENSIONS.contains(&extension.as_str()),
+        BiSignatureVersion::V3 => V3_INCLUDED_EXTENSIONS.contains(&extension.as_str()),
+    }
+}
+
+/// PBO which failed verification.
+#[derive(Debug)]
+pub struct SignatureFailure {
+    pub path: PathBuf,
+    pub error: anyhow::Error,
+}
+
+/// Verifies all PBOs of an addons directory against the given keys, a PBO passes if any of its
+/// `<name>.pbo.<authority>.bisign` signatures is valid for a key of the same authority.
+pub fn verify_addons(directory: &Path, keys: &[BiPublicKey]) -> Result<Vec<SignatureFailure>> {
+    let mut failures = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if !path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("pbo"))
+        {
+            continue;
+        }
+
+        if let Err(error) = verify_pbo(&path, keys) {
+            failures.push(SignatureFailure { path, error });
+        }
+    }
+
+    Ok(failures)
+}
+
+/// Verifies a PBO against the given keys, using the signatures next to it.
+pub fn verify_pbo(path: &Path, keys: &[BiPublicKey]) -> Result<()> {
+    let mut error = None;
+    for key in keys {
+        let mut signature_path = path.as_os_str().to_owned();
+        signature_path.push(format!(".{}.bisign", key.authority));
+        let signature_path = PathBuf::from(signature_path);
+        if !signature_path.exists() {
+            continue;
+        }
+
+        match BiSignature::open(&signature_path)
+            .and_then(|signature| signature.verify(key, &mut BufReader::new(File::open(path)?)))
+        {
+            Ok(()) => return Ok(()),
+            Err(signature_error) => error = Some(signature_error),
+        }
+    }
+
+    Err(error.unwrap_or_else(|| SignatureError::MissingSignature.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::pbo::tests::write_pbo;
+
+    /// Modulus of a 512-bit test key with exponent 65537, little-endian.
+    const MODULUS: &str = "f12fc0944437c83115eadfad148724139c291133b6d4699f667f5b513edf6903\
+        593779182cf507a9878423b45e65a6bffed8fc51acc79fdaa73edf0a69030bb4";
+
+    /// Signatures of the test PBO by version, little-endian.
+    const SIGNATURES_V2: [&str; 3] = [
+        "7333713fa1482d2b40f32c964815e05d0962eaff2e8e3e80cdce59faba956ed1\
+        43d290ace6acdd4b239bf854c00e3f59647161333ac6b049aa15f1f80c96ab08",
+        "e8497617908e405e98d56a7a34de8135a149b13639903dad90c47d88eb793ba8\
+        cbf6428b9d0d2e4d4777b28babb4217f74011d2af024dcdcfdd34d969261ea65",
+        "fdd43fe23fdde7ba340d0ec34e96bb789babd353f6e39ce7437feb558c80c63d\
+        f38d5ccb2bf989f24a73462c760d540e5c38e0f007affb5da0e29f39d7ffe486",
+    ];
+    const SIGNATURES_V3: [&str; 3] = [
+        SIGNATURES_V2[0],
+        SIGNATURES_V2[1],
+        "7da787bdd6c1dc61ddecf1ede83fe2aafbf5b1708b4c29393be7cf75337408b5\
+        2e4a519cedc512862b589a22791a21db6548e3bb431fb05695e429bfba580d9d",
+    ];
+
+    fn hex(value: &str) -> Vec<u8> {
+        (0..value.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&value[index..index + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn write_key(output: &mut Vec<u8>, authority: &str, bit_length: u32, modulus: &[u8]) {
+        output.extend_from_slice(authority.as_bytes());
+        output.push(0);
+        output.extend_from_slice(&(20 + modulus.len() as u32).to_le_bytes());
+        output.extend_from_slice(&[PUBLIC_KEY_BLOB, 2, 0, 0]);
+        output.extend_from_slice(&CALG_RSA_SIGN.to_le_bytes());
+        output.extend_from_slice(b"RSA1");
+        output.extend_from_slice(&bit_length.to_le_bytes());
+        output.extend_from_slice(&65537u32.to_le_bytes());
+        output.extend_from_slice(modulus);
+    }
+
+    fn write_signature(version: u32, signatures: [&str; 3]) -> Vec<u8> {
+        let mut output = Vec::new();
+        write_key(&mut output, "vixen", 512, &hex(MODULUS));
+        for (index, signature) in signatures.iter().enumerate() {
+            if index == 1 {
+                output.extend_from_slice(&version.to_le_bytes());
+            }
+            let signature = hex(signature);
+            output.extend_from_slice(&(signature.len() as u32).to_le_bytes());
+            output.extend_from_slice(&signature);
+        }
+        output
+    }
+
+    fn test_pbo() -> Vec<u8> {
+        write_pbo(
+            "vixen\\test",
+            &[
+                ("config.cpp", false, b"class CfgPatches {};"),
+                ("Data\\tex.paa", false, b"PAADATA"),
+                ("script.sqf", false, b"hint 'hi';"),
+            ],
+        )
+    }
+
+    #[test]
+    fn read_key() {
+        let mut data = Vec::new();
+        write_key(&mut data, "vixen", 512, &hex(MODULUS));
+        let key = BiPublicKey::read_from(&mut data.as_slice()).unwrap();
+        assert_eq!(key.authority, "vixen");
+        assert_eq!(key.exponent, 65537);
+        assert_eq!(key.length, 64);
+        assert_eq!(key.modulus, BigUint::from_bytes_le(&hex(MODULUS)));
+        assert_eq!(key.modulus.bits(), 512);
+
+        // Blob type after the authority and blob length
+        data[10] = 0x07;
+        assert!(BiPublicKey::read_from(&mut data.as_slice()).is_err());
+    }
+
+    #[test]
+    fn oversized_lengths() {
+        // Lengths are rejected before anything is allocated
+        let mut data = Vec::new();
+        write_key(&mut data, "vixen", u32::MAX, &[]);
+        let error = BiPublicKey::read_from(&mut data.as_slice()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SignatureError::KeyTooLong(_))
+        ));
+
+        let mut data = Vec::new();
+        write_key(&mut data, "vixen", 512, &hex(MODULUS));
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        let error = BiSignature::read_from(&mut data.as_slice()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SignatureError::KeyTooLong(_))
+        ));
+    }
+
+    #[test]
+    fn hashes() {
+        let pbo = test_pbo();
+        let hashes = |pbo: &[u8], version| {
+            pbo_hashes(&mut Cursor::new(pbo), version)
+                .unwrap()
+                .map(|hash| {
+                    hash.iter()
+                        .map(|byte| format!("{byte:02x}"))
+                        .collect::<String>()
+                })
+        };
+
+        // Version 2 hashes all files but textures, models and sounds, version 3 only scripts and
+        // configs
+        assert_eq!(
+            hashes(&pbo, BiSignatureVersion::V2),
+            [
+                "74b14d9b5ccc72735ce8164db1728a655b334294",
+                "3e974ea045ae2896a4a24b02a860d7510deaad60",
+                "0e71a4a838380d96e9d629e525d1f66d522e5545",
+            ]
+        );
+        assert_eq!(
+            hashes(&pbo, BiSignatureVersion::V3)[2],
+            "c33daeb7446c5b7c0fb663db57268f16cd342bb1"
+        );
+
+        // Hashes of "nothing" and "gnihton" without hashed files
+        let pbo = write_pbo("", &[("tex.paa", false, b"PAADATA")]);
+        assert_eq!(
+            hashes(&pbo, BiSignatureVersion::V2),
+            [
+                "58c2b7952d88a9e920a8f9d4766f2d7c14959d40",
+                "a7d8e24874c26398d1106eb6482246daaa1b4c4e",
+                "4e8ee4e4b3fd5d584f5766581e3fa8e45cd7d00c",
+            ]
+        );
+        assert_eq!(
+            hashes(&pbo, BiSignatureVersion::V3)[2],
+            "c8156976810b393040419af8b37cc9457f64cdcf"
+        );
+    }
+
+    #[test]
+    fn verify() {
+        let mut key = Vec::new();
+        write_key(&mut key, "vixen", 512, &hex(MODULUS));
+        let key = BiPublicKey::read_from(&mut key.as_slice()).unwrap();
+        let mut pbo = test_pbo();
+
+        for (version, signatures) in [
+            (BiSignatureVersion::V2, SIGNATURES_V2),
+            (BiSignatureVersion::V3, SIGNATURES_V3),
+        ] {
+            let data = write_signature(
+                match version {
+                    BiSignatureVersion::V2 => 2,
+                    BiSignatureVersion::V3 => 3,
+                },
+                signatures,
+            );
+            let signature = BiSignature::read_from(&mut data.as_slice()).unwrap();
+            assert_eq!(signature.version, version);
+            assert_eq!(signature.key, key);
+            signature.verify(&key, &mut Cursor::new(&pbo)).unwrap();
+        }
+
+        // Modified data fails the first hash, which covers everything
+        let length = pbo.len();
+        pbo[length - 22] ^= 1;
+        let data = write_signature(2, SIGNATURES_V2);
+        let signature = BiSignature::read_from(&mut data.as_slice()).unwrap();
+        let error = signature.verify(&key, &mut Cursor::new(&pbo)).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SignatureError::HashMismatch(1))
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::config::{ConfigClass, ConfigCursor, ConfigValue};
+
+/// Skeleton of a model, as defined by `CfgSkeletons` in model.cfg.
+///
+/// Bones are named selections of the model, and have no rest transform.
+#[derive(Clone, Debug, Default)]
+pub struct P3dSkeleton {
+    pub bones: Vec<P3dBone>,
+}
+
+#[derive(Clone, Debug)]
+pub struct P3dBone {
+    pub name: String,
+    /// Index of the parent bone.
+    pub parent: Option<usize>,
+}
+
+impl P3dSkeleton {
+    /// Reads the skeleton of a model from `CfgModels` and `CfgSkeletons`, the model name is the
+    /// file stem of the p3d.
+    pub fn from_model_cfg(config: &ConfigClass, model_name: &str) -> Option<Self> {
+        let root = ConfigCursor::new(config);
+        let skeleton_name = root
+            .class("CfgModels")?
+            .class(model_name)?
+            .value("skeletonName")?
+            .as_str()?;
+        let skeletons = root.class("CfgSkeletons")?;
+
+        // Collect pairs of bone and parent names, inherited skeletons first
+        let mut bone_names = Vec::new();
+        collect_bones(&skeletons, skeleton_name, &mut bone_names, 0);
+        if bone_names.is_empty() {
+            return None;
+        }
+
+        let mut skeleton = Self::default();
+        for (name, _) in &bone_names {
+            if skeleton.bone(name).is_none() {
+                skeleton.bones.push(P3dBone {
+                    name: name.clone(),
+                    parent: None,
+                });
+            }
+        }
+        for (name, parent_name) in &bone_names {
+            let (Some(bone), Some(parent)) = (skeleton.bone(name), skeleton.bone(parent_name))
+            else {
+                continue;
+            };
+
+            // Reject parents which would result in a cycle
+            let mut ancestor = Some(parent);
+            while let Some(index) = ancestor {
+                if index == bone {
+                    break;
+                }
+                ancestor = skeleton.bones[index].parent;
+            }
+            if ancestor.is_none() {
+                skeleton.bones[bone].parent = Some(parent);
+            }
+        }
+
+        Some(skeleton)
+    }
+
+    /// Returns the index of a bone, names are case-insensitive.
+    pub fn bone(&self, name: &str) -> Option<usize> {
+        self.bones
+            .iter()
+            .position(|bone| bone.name.eq_ignore_ascii_case(name))
+    }
+}
+
+fn collect_bones(
+    skeletons: &ConfigCursor,
+    name: &str,
+    bones: &mut Vec<(String, String)>,
+    depth: usize,
+) {
+    let Some(skeleton) = skeletons.class(name) else {
+        return;
+    };
+
+    if depth < 16 {
+        if let Some(inherit) = skeleton
+            .value("skeletonInherit")
+            .and_then(ConfigValue::as_str)
+            .filter(|inherit| !inherit.is_empty())
+        {
+            collect_bones(skeletons, inherit, bones, depth + 1);
+        }
+    }
+    if let Some(values) = skeleton
+        .value("skeletonBones")
+        .and_then(ConfigValue::as_array)
+    {
+        for pair in values.chunks_exact(2) {
+            if let (Some(name), Some(parent)) = (pair[0].as_str(), pair[1].as_str()) {
+                if !name.is_empty() {
+                    bones.push((name.to_string(), parent.to_string()));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use bevy::{
+    asset::{AssetIo, AssetIoError, BoxedFuture, FileType, Metadata},
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    pbo::{Pbo, PboEntry},
+    texheaders::TexHeaders,
+};
+
+/// Mounts the PBOs of the game and mods as asset source, and fills [`TexHeaders`] with the
+/// headers of their textures.
+///
+/// Has to be added before the `AssetPlugin`, e.g. with
+/// `DefaultPlugins.build().add_before::<AssetPlugin, _>(PboAssetIoPlugin { .. })`, which then
+/// keeps the asset server created by this plugin.
+#[derive(Default)]
+pub struct PboAssetIoPlugin {
+    /// Game and mod directories in load order, later ones override files of earlier ones.
+    pub directories: Vec<PathBuf>,
+    /// Asset folder and change watching of files outside of PBOs, should match the settings of
+    /// the `AssetPlugin`.
+    pub asset_plugin: AssetPlugin,
+}
+
+impl Plugin for PboAssetIoPlugin {
+    fn build(&self, app: &mut App) {
+        let asset_io = PboAssetIo::new(
+            self.asset_plugin.create_platform_default_asset_io(),
+            &self.directories,
+        );
+        app.insert_resource(asset_io.tex_headers())
+            .insert_resource(AssetServer::new(asset_io));
+    }
+}
+
+/// Asset io reading files from PBOs by their path including the prefix, e.g.
+/// `ca/chernarus/data/layers/s_000_000_lco.paa`, other files are read from the parent.
+pub struct PboAssetIo {
+    parent: Box<dyn AssetIo>,
+    pbos: Vec<(PathBuf, Pbo)>,
+    /// Index of the PBO and entry by lowercase path with forward slashes.
+    entries: HashMap<String, (usize, usize)>,
+}
+
+impl PboAssetIo {
+    /// Mounts the PBOs of the directories, and their `addons` directory, in order.
+    pub fn new(parent: Box<dyn AssetIo>, directories: &[PathBuf]) -> Self {
+        let mut asset_io = Self {
+            parent,
+            pbos: Vec::new(),
+            entries: HashMap::default(),
+        };
+        for directory in directories {
+            for path in pbo_paths(directory) {
+                if let Err(error) = asset_io.mount(&path) {
+                    warn!("Failed to mount {path:?}: {error}");
+                }
+            }
+        }
+        asset_io
+    }
+
+    /// Mounts a PBO, overriding files of already mounted ones.
+    pub fn mount(&mut self, path: &Path) -> Result<()> {
+        let pbo = Pbo::read_from(&mut BufReader::new(File::open(path)?))?;
+        let prefix = pbo.prefix().to_ascii_lowercase();
+        for (entry_index, entry) in pbo.entries.iter().enumerate() {
+            let name = entry.name.replace('\\', "/").to_ascii_lowercase();
+            let name = if prefix.is_empty() {
+                name
+            } else {
+                format!("{prefix}/{name}")
+            };
+            self.entries.insert(name, (self.pbos.len(), entry_index));
+        }
+        self.pbos.push((path.to_path_buf(), pbo));
+        Ok(())
+    }
+
+    /// Returns the texture headers of all mounted PBOs, in mount order.
+    pub fn tex_headers(&self) -> TexHeaders {
+        let mut tex_headers = TexHeaders::default();
+        for (path, pbo) in &self.pbos {
+            let headers = File::open(path)
+                .map_err(Into::into)
+                .and_then(|file| pbo.read_tex_headers(&mut BufReader::new(file)));
+            match headers {
+                Ok(Some(headers)) => tex_headers.extend(headers),
+                Ok(None) => {}
+                Err(error) => warn!("Failed to read texture headers of {path:?}: {error}"),
+            }
+        }
+        tex_headers
+    }
+
+    fn entry(&self, path: &Path) -> Option<(&Path, &Pbo, &PboEntry)> {
+        let (pbo_index, entry_index) = self.entries.get(&normalize_path(path))?;
+        let (pbo_path, pbo) = &self.pbos[*pbo_index];
+        Some((pbo_path, pbo, &pbo.entries[*entry_index]))
+    }
+}
+
+impl AssetIo for PboAssetIo {
+    fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+        let Some((pbo_path, pbo, entry)) = self.entry(path) else {
+            return self.parent.load_path(path);
+        };
+        Box::pin(async move {
+            let mut input = BufReader::new(File::open(pbo_path)?);
+            pbo.read(&mut input, entry).map_err(|error| {
+                AssetIoError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+            })
+        })
+    }
+
+    fn read_directory(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+        let directory = normalize_path(path);
+        let children: HashSet<_> = self
+            .entries
+            .keys()
+            .filter_map(|name| {
+                let name = if directory.is_empty() {
+                    name.as_str()
+                } else {
+                    name.strip_prefix(&directory)?.strip_prefix('/')?
+                };
+                let child = name.split('/').next()?;
+                Some(path.join(child))
+            })
+            .collect();
+        if children.is_empty() {
+            return self.parent.read_directory(path);
+        }
+        Ok(Box::new(children.into_iter()))
+    }
+
+    fn get_metadata(&self, path: &Path) -> Result<Metadata, AssetIoError> {
+        let name = normalize_path(path);
+        if self.entries.contains_key(&name) {
+            return Ok(Metadata::new(FileType::File));
+        }
+        let directory = format!("{name}/");
+        if self.entries.keys().any(|name| name.starts_with(&directory)) {
+            return Ok(Metadata::new(FileType::Directory));
+        }
+        self.parent.get_metadata(path)
+    }
+
+    fn watch_path_for_changes(&self, path: &Path) -> Result<(), AssetIoError> {
+        // PBOs are not watched
+        if self.entry(path).is_some() {
+            return Ok(());
+        }
+        self.parent.watch_path_for_changes(path)
+    }
+
+    fn watch_for_changes(&self) -> Result<(), AssetIoError> {
+        self.parent.watch_for_changes()
+    }
+}
+
+/// Returns the PBOs in a directory and its `addons` directory, sorted by name.
+fn pbo_paths(directory: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = [directory.to_path_buf(), directory.join("addons")]
+        .iter()
+        .filter_map(|directory| fs::read_dir(directory).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("pbo"))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn normalize_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .trim_start_matches(['\\', '/'])
+        .trim_end_matches(['\\', '/'])
+        .replace('\\', "/")
+        .to_ascii_lowercase()
+}
@@ -0,0 +1,401 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use bevy::{
+    asset::{AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+
+use crate::config::{ConfigClass, ConfigCursor, ConfigValue};
+
+/// Registers the SQM loader and the mission asset type.
+#[derive(Default)]
+pub struct SqmPlugin {
+    /// Game config, used to resolve models of vehicle classes.
+    pub config: Option<Arc<ConfigClass>>,
+}
+
+impl Plugin for SqmPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<SqmMission>().add_asset_loader(SqmLoader {
+            config: self.config.clone(),
+        });
+    }
+}
+
+/// Bohemia mission loader, for text and rap-binarized mission.sqm.
+///
+/// Objects are placed in the scene by the model of their class in `CfgVehicles` of the game config,
+/// objects of unknown classes are placed without a model.
+#[derive(Default)]
+pub struct SqmLoader {
+    pub config: Option<Arc<ConfigClass>>,
+}
+
+impl AssetLoader for SqmLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move { load_sqm(bytes, load_context, self.config.as_deref()).await })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["sqm"]
+    }
+}
+
+/// Mission, positions are in the P3D coordinate system, with the altitude as y.
+#[derive(TypeUuid, Clone, Debug, Default)]
+#[uuid = "7b1f0d4e-6a93-4c28-b5e7-3d9a2c6f1e84"]
+pub struct SqmMission {
+    pub version: i32,
+    pub addons: Vec<String>,
+    pub groups: Vec<SqmGroup>,
+    /// Objects which are not part of a group, e.g. empty vehicles.
+    pub objects: Vec<SqmObject>,
+    pub markers: Vec<SqmMarker>,
+    pub triggers: Vec<SqmTrigger>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SqmGroup {
+    pub side: String,
+    pub units: Vec<SqmObject>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SqmObject {
+    pub id: Option<i32>,
+    /// Class name in `CfgVehicles`.
+    pub type_: String,
+    pub name: Option<String>,
+    pub side: String,
+    pub position: Vec3,
+    /// Azimuth in radians, clockwise from north.
+    pub azimuth: f32,
+    pub player: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SqmMarker {
+    pub name: String,
+    pub type_: String,
+    pub text: String,
+    pub position: Vec3,
+    pub size: Vec2,
+    pub angle: f32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SqmTrigger {
+    pub name: String,
+    pub text: String,
+    pub position: Vec3,
+    pub size: Vec2,
+    pub angle: f32,
+    pub rectangular: bool,
+    pub activation: String,
+    pub condition: String,
+    pub on_activation: String,
+}
+
+impl SqmMission {
+    /// Reads a mission, both the entity format of version 51 and above, and the older format with
+    /// separate groups, vehicles, markers and sensors are supported.
+    pub fn from_config(config: &ConfigClass) -> Self {
+        let root = ConfigCursor::new(config);
+        let mut mission = Self {
+            version: root
+                .value("version")
+                .and_then(ConfigValue::as_i32)
+                .unwrap_or_default(),
+            addons: strings(root.value("addons")),
+            ..default()
+        };
+        let Some(mission_class) = root.class("Mission") else {
+            return mission;
+        };
+
+        if let Some(entities) = mission_class.class("Entities") {
+            mission.read_entities(&entities, None);
+        }
+
+        for group in items(mission_class.class("Groups")) {
+            mission.groups.push(SqmGroup {
+                side: string(&group, "side"),
+                units: items(group.class("Vehicles"))
+                    .iter()
+                    .map(read_legacy_object)
+                    .collect(),
+            });
+        }
+        for vehicle in items(mission_class.class("Vehicles")) {
+            mission.objects.push(read_legacy_object(&vehicle));
+        }
+        for marker in items(mission_class.class("Markers")) {
+            mission.markers.push(SqmMarker {
+                name: string(&marker, "name"),
+                type_: string(&marker, "type"),
+                text: string(&marker, "text"),
+                position: position(marker.value("position")),
+                size: Vec2::new(number(&marker, "a", 1.0), number(&marker, "b", 1.0)),
+                angle: number(&marker, "angle", 0.0).to_radians(),
+            });
+        }
+        for sensor in items(mission_class.class("Sensors")) {
+            mission.triggers.push(SqmTrigger {
+                name: string(&sensor, "name"),
+                text: string(&sensor, "text"),
+                position: position(sensor.value("position")),
+                size: Vec2::new(number(&sensor, "a", 0.0), number(&sensor, "b", 0.0)),
+                angle: number(&sensor, "angle", 0.0).to_radians(),
+                rectangular: number(&sensor, "rectangular", 0.0) != 0.0,
+                activation: string(&sensor, "activationBy"),
+                condition: string(&sensor, "expCond"),
+                on_activation: string(&sensor, "expActiv"),
+            });
+        }
+
+        mission
+    }
+
+    /// Returns all objects, including units of groups.
+    pub fn all_objects(&self) -> impl Iterator<Item = &SqmObject> {
+        self.groups
+            .iter()
+            .flat_map(|group| group.units.iter())
+            .chain(self.objects.iter())
+    }
+
+    fn read_entities(&mut self, entities: &ConfigCursor, group: Option<usize>) {
+        for entity in items(Some(entities.clone())) {
+            let attributes = entity.class("Attributes");
+            let attribute = |name: &str| {
+                attributes
+                    .as_ref()
+                    .map(|attributes| string(attributes, name))
+                    .unwrap_or_default()
+            };
+            let position_info = entity.class("PositionInfo");
+            let position = position(
+                position_info
+                    .as_ref()
+                    .and_then(|position_info| position_info.value("position")),
+            );
+            let angles = position_info
+                .as_ref()
+                .and_then(|position_info| position_info.value("angles"))
+                .map_or(Vec3::ZERO, |angles| self::position(Some(angles)));
+
+            match string(&entity, "dataType").to_ascii_lowercase().as_str() {
+                "group" => {
+                    self.groups.push(SqmGroup {
+                        side: string(&entity, "side"),
+                        units: Vec::new(),
+                    });
+                    if let Some(group_entities) = entity.class("Entities") {
+                        self.read_entities(&group_entities, Some(self.groups.len() - 1));
+                    }
+                }
+                // Layers only organize entities in the editor
+                "layer" => {
+                    if let Some(layer_entities) = entity.class("Entities") {
+                        self.read_entities(&layer_entities, group);
+                    }
+                }
+                "object" | "logic" => {
+                    let name = attribute("name");
+                    let object = SqmObject {
+                        id: entity.value("id").and_then(ConfigValue::as_i32),
+                        type_: string(&entity, "type"),
+                        name: (!name.is_empty()).then_some(name),
+                        side: string(&entity, "side"),
+                        position,
+                        azimuth: angles.y,
+                        player: !attribute("isPlayer").is_empty() && attribute("isPlayer") != "0",
+                    };
+                    match group {
+                        Some(group) => self.groups[group].units.push(object),
+                        None => self.objects.push(object),
+                    }
+                }
+                "marker" => self.markers.push(SqmMarker {
+                    name: string(&entity, "name"),
+                    type_: string(&entity, "type"),
+                    text: string(&entity, "text"),
+                    position,
+                    size: Vec2::new(number(&entity, "a", 1.0), number(&entity, "b", 1.0)),
+                    angle: number(&entity, "angle", 0.0).to_radians(),
+                }),
+                "trigger" => self.triggers.push(SqmTrigger {
+                    name: attribute("name"),
+                    text: attribute("text"),
+                    position,
+                    size: Vec2::new(
+                        attributes
+                            .as_ref()
+                            .map_or(0.0, |attributes| number(attributes, "sizeA", 0.0)),
+                        attributes
+                            .as_ref()
+                            .map_or(0.0, |attributes| number(attributes, "sizeB", 0.0)),
+                    ),
+                    angle: angles.y,
+                    rectangular: attribute("isRectangle") == "1",
+                    activation: attribute("activationBy"),
+                    condition: attribute("condition"),
+                    on_activation: attribute("onActivation"),
+                }),
+                _ => {}
+            }
+        }
+    }
+}
+
+async fn load_sqm<'a, 'b>(
+    bytes: &'a [u8],
+    load_context: &'a mut LoadContext<'b>,
+    config: Option<&'a ConfigClass>,
+) -> Result<()> {
+    let mission = SqmMission::from_config(&ConfigClass::from_bytes(bytes)?);
+
+    let mut world = World::default();
+    let root = world.spawn(SpatialBundle::VISIBLE_IDENTITY).id();
+    let mut objects = Vec::new();
+    let mut models: Vec<String> = Vec::new();
+    for object in mission.all_objects() {
+        let mut entity = world.spawn((
+            SpatialBundle::from_transform(
+                Transform::from_translation(object.position)
+                    .with_rotation(Quat::from_rotation_y(-object.azimuth)),
+            ),
+            Name::new(object.name.clone().unwrap_or_else(|| object.type_.clone())),
+        ));
+        if let Some(model) = config.and_then(|config| model_path(config, &object.type_)) {
+            entity.with_children(|parent| {
+                parent.spawn(SceneBundle {
+                    scene: load_context
+                        .get_handle(AssetPath::new(model.clone().into(), Some("Scene".into()))),
+                    ..default()
+                });
+            });
+            if !models.contains(&model) {
+                models.push(model);
+            }
+        }
+        objects.push(entity.id());
+    }
+    world.entity_mut(root).push_children(&objects);
+
+    // Handles do not start loading, models are loaded as dependencies of the scene
+    load_context.set_labeled_asset(
+        "Scene",
+        LoadedAsset::new(Scene::new(world)).with_dependencies(
+            models
+                .into_iter()
+                .map(|model| AssetPath::new(model.into(), None))
+                .collect(),
+        ),
+    );
+    load_context.set_default_asset(LoadedAsset::new(mission));
+
+    Ok(())
+}
+
+/// Resolves the model of a vehicle class, or the model itself for simple objects.
+fn model_path(config: &ConfigClass, type_: &str) -> Option<String> {
+    let model = if type_.to_ascii_lowercase().ends_with(".p3d") {
+        type_
+    } else {
+        ConfigCursor::new(config)
+            .class("CfgVehicles")?
+            .class(type_)?
+            .value("model")?
+            .as_str()?
+    };
+    if model.is_empty() {
+        return None;
+    }
+
+    let mut path = model
+        .trim_start_matches('\\')
+        .replace('\\', "/")
+        .to_ascii_lowercase();
+    if !path.ends_with(".p3d") {
+        path.push_str(".p3d");
+    }
+    Some(path)
+}
+
+fn read_legacy_object(class: &ConfigCursor) -> SqmObject {
+    let name = string(class, "text");
+    SqmObject {
+        id: class.value("id").and_then(ConfigValue::as_i32),
+        type_: string(class, "vehicle"),
+        name: (!name.is_empty()).then_some(name),
+        side: string(class, "side"),
+        position: position(class.value("position")),
+        azimuth: number(class, "azimut", 0.0).to_radians(),
+        player: !string(class, "player").is_empty(),
+    }
+}
+
+/// Returns the `ItemN` classes of a list, in order.
+fn items<'a>(list: Option<ConfigCursor<'a>>) -> Vec<ConfigCursor<'a>> {
+    let Some(list) = list else {
+        return Vec::new();
+    };
+    let count = list
+        .value("items")
+        .and_then(ConfigValue::as_i32)
+        .unwrap_or_default();
+    (0..count)
+        .filter_map(|index| list.class(&format!("Item{index}")))
+        .collect()
+}
+
+fn string(class: &ConfigCursor, name: &str) -> String {
+    class
+        .value(name)
+        .and_then(|value| match value {
+            ConfigValue::String(value) => Some(value.clone()),
+            ConfigValue::Int(value) => Some(value.to_string()),
+            ConfigValue::Float(value) => Some(value.to_string()),
+            ConfigValue::Array(_) => None,
+        })
+        .unwrap_or_default()
+}
+
+fn strings(value: Option<&ConfigValue>) -> Vec<String> {
+    value
+        .and_then(ConfigValue::as_array)
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(ConfigValue::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn number(class: &ConfigCursor, name: &str, default: f32) -> f32 {
+    class
+        .value(name)
+        .and_then(ConfigValue::as_f32)
+        .unwrap_or(default)
+}
+
+/// Positions are stored as x, altitude, z.
+fn position(value: Option<&ConfigValue>) -> Vec3 {
+    let values = value.and_then(ConfigValue::as_array).unwrap_or_default();
+    let component = |index: usize| {
+        values
+            .get(index)
+            .and_then(ConfigValue::as_f32)
+            .unwrap_or_default()
+    };
+    Vec3::new(component(0), component(1), component(2))
+}
@@ -0,0 +1,207 @@
+use bevy::{
+    asset::load_internal_asset,
+    math::Rect,
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::{AsBindGroup, ShaderRef},
+};
+
+use crate::config::{ConfigClass, ConfigCursor, ConfigValue};
+
+pub const TERRAIN_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x5d2b8e14c7a93f60);
+
+/// Largest number of surface layers of a tile.
+pub const MAXIMUM_TERRAIN_LAYERS: usize = 4;
+
+/// Registers the terrain material and its shader.
+#[derive(Default)]
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            TERRAIN_SHADER_HANDLE,
+            "terrain.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.add_plugin(MaterialPlugin::<TerrainMaterial>::default());
+    }
+}
+
+/// Splatting material of a terrain tile, blending up to four surface layers by the mask, which
+/// modulate the satellite texture close to the camera.
+///
+/// The first layer covers what is not covered by the others, which are weighted by the red, green
+/// and blue channel of the mask.
+#[derive(AsBindGroup, TypeUuid, Clone, Debug)]
+#[uuid = "8c4e1f27-5b9a-4d63-a2e8-0f7d3b6c9e15"]
+pub struct TerrainMaterial {
+    /// Minimum x and z of the tile, its size, and the overlap of the textures.
+    #[uniform(0)]
+    pub tile: Vec4,
+    /// Repetitions of the layer textures per meter.
+    #[uniform(0)]
+    pub detail_scale: f32,
+    /// Distance at which only the satellite texture is visible.
+    #[uniform(0)]
+    pub fade_distance: f32,
+    /// Number of used layers.
+    #[uniform(0)]
+    pub layer_count: u32,
+    #[texture(1)]
+    #[sampler(2)]
+    pub satellite: Option<Handle<Image>>,
+    #[texture(3)]
+    #[sampler(4)]
+    pub mask: Option<Handle<Image>>,
+    #[texture(5)]
+    #[sampler(6)]
+    pub layer0: Option<Handle<Image>>,
+    #[texture(7)]
+    #[sampler(8)]
+    pub layer1: Option<Handle<Image>>,
+    #[texture(9)]
+    #[sampler(10)]
+    pub layer2: Option<Handle<Image>>,
+    #[texture(11)]
+    #[sampler(12)]
+    pub layer3: Option<Handle<Image>>,
+}
+
+impl Material for TerrainMaterial {
+    fn fragment_shader() -> ShaderRef {
+        TERRAIN_SHADER_HANDLE.typed().into()
+    }
+}
+
+/// Layout of the satellite and mask tiles of a terrain, named `s_xxx_yyy_lco.paa` and
+/// `m_xxx_yyy_lca.paa`.
+///
+/// Tiles lie in the xz plane, tile x and y grow along x and z, starting at the origin.
+#[derive(Clone, Debug)]
+pub struct TerrainTiling {
+    /// Number of tiles along x and z.
+    pub tiles: UVec2,
+    /// Size of a tile without overlap in meters.
+    pub tile_size: f32,
+    /// Overlap with the neighbouring tiles on each side, as a fraction of the texture size.
+    pub overlap: f32,
+    /// Directory of the tiles, e.g. `ca/chernarus/data/layers`.
+    pub directory: String,
+    /// Repetitions of the layer textures per meter.
+    pub detail_scale: f32,
+    /// Distance at which only the satellite texture is visible.
+    pub fade_distance: f32,
+}
+
+impl Default for TerrainTiling {
+    fn default() -> Self {
+        Self {
+            tiles: UVec2::ONE,
+            tile_size: 512.0,
+            overlap: 0.0,
+            directory: String::new(),
+            detail_scale: 0.1,
+            fade_distance: 200.0,
+        }
+    }
+}
+
+impl TerrainTiling {
+    pub fn satellite_path(&self, tile: UVec2) -> String {
+        self.tile_path("s", tile, "lco")
+    }
+
+    pub fn mask_path(&self, tile: UVec2) -> String {
+        self.tile_path("m", tile, "lca")
+    }
+
+    /// Returns the area covered by a tile, without overlap.
+    pub fn tile_rect(&self, tile: UVec2) -> Rect {
+        let min = tile.as_vec2() * self.tile_size;
+        Rect::from_corners(min, min + self.tile_size)
+    }
+
+    /// Creates the material of a tile, layers are the paths of their color textures, in mask
+    /// channel order.
+    pub fn material(
+        &self,
+        tile: UVec2,
+        layers: &[&str],
+        asset_server: &AssetServer,
+    ) -> TerrainMaterial {
+        let mut layers = layers
+            .iter()
+            .take(MAXIMUM_TERRAIN_LAYERS)
+            .map(|path| Some(asset_server.load(texture_path(path).as_str())));
+        let min = self.tile_rect(tile).min;
+        TerrainMaterial {
+            tile: Vec4::new(min.x, min.y, self.tile_size, self.overlap),
+            detail_scale: self.detail_scale,
+            fade_distance: self.fade_distance,
+            layer_count: layers.len() as u32,
+            satellite: Some(asset_server.load(self.satellite_path(tile).as_str())),
+            mask: Some(asset_server.load(self.mask_path(tile).as_str())),
+            layer0: layers.next().flatten(),
+            layer1: layers.next().flatten(),
+            layer2: layers.next().flatten(),
+            layer3: layers.next().flatten(),
+        }
+    }
+
+    /// Creates the material of a tile from its RVMAT, which references the satellite texture in
+    /// the first stage, the mask in the second, followed by the normal and color texture of each
+    /// layer.
+    pub fn material_from_rvmat(
+        &self,
+        tile: UVec2,
+        rvmat: &ConfigClass,
+        asset_server: &AssetServer,
+    ) -> TerrainMaterial {
+        let rvmat = ConfigCursor::new(rvmat);
+        let stage = |index: usize| {
+            rvmat
+                .class(&format!("Stage{index}"))
+                .and_then(|stage| stage.value("texture"))
+                .and_then(ConfigValue::as_str)
+                .filter(|texture| !texture.is_empty())
+        };
+        let layers: Vec<_> = (0..MAXIMUM_TERRAIN_LAYERS)
+            .map_while(|layer| stage(3 + layer * 2))
+            .collect();
+
+        let mut material = self.material(tile, &layers, asset_server);
+        if let Some(satellite) = stage(0) {
+            material.satellite = Some(asset_server.load(texture_path(satellite).as_str()));
+        }
+        if let Some(mask) = stage(1) {
+            material.mask = Some(asset_server.load(texture_path(mask).as_str()));
+        }
+        material
+    }
+
+    fn tile_path(&self, prefix: &str, tile: UVec2, suffix: &str) -> String {
+        let name = format!("{prefix}_{:03}_{:03}_{suffix}.paa", tile.x, tile.y);
+        let directory = self.directory.trim_end_matches(['/', '\\']);
+        if directory.is_empty() {
+            name
+        } else {
+            texture_path(&format!("{directory}/{name}"))
+        }
+    }
+}
+
+/// Normalizes a texture path of a material, source images are referenced by their PAA.
+fn texture_path(path: &str) -> String {
+    let path = path
+        .trim_start_matches('\\')
+        .replace('\\', "/")
+        .to_ascii_lowercase();
+    match path.rsplit_once('.') {
+        Some((stem, "png" | "tga")) => format!("{stem}.paa"),
+        _ => path,
+    }
+}
@@ -0,0 +1,94 @@
+#import bevy_pbr::mesh_view_bindings
+#import bevy_pbr::mesh_bindings
+
+#import bevy_pbr::pbr_types
+#import bevy_pbr::utils
+#import bevy_pbr::clustered_forward
+#import bevy_pbr::lighting
+#import bevy_pbr::shadows
+#import bevy_pbr::pbr_functions
+
+struct TerrainMaterial {
+    // Minimum x and z, size, and overlap of the tile
+    tile: vec4<f32>,
+    detail_scale: f32,
+    fade_distance: f32,
+    layer_count: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> material: TerrainMaterial;
+@group(1) @binding(1)
+var satellite_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var satellite_sampler: sampler;
+@group(1) @binding(3)
+var mask_texture: texture_2d<f32>;
+@group(1) @binding(4)
+var mask_sampler: sampler;
+@group(1) @binding(5)
+var layer0_texture: texture_2d<f32>;
+@group(1) @binding(6)
+var layer0_sampler: sampler;
+@group(1) @binding(7)
+var layer1_texture: texture_2d<f32>;
+@group(1) @binding(8)
+var layer1_sampler: sampler;
+@group(1) @binding(9)
+var layer2_texture: texture_2d<f32>;
+@group(1) @binding(10)
+var layer2_sampler: sampler;
+@group(1) @binding(11)
+var layer3_texture: texture_2d<f32>;
+@group(1) @binding(12)
+var layer3_sampler: sampler;
+
+struct FragmentInput {
+    @builtin(front_facing) is_front: bool,
+    @builtin(position) frag_coord: vec4<f32>,
+    #import bevy_pbr::mesh_vertex_output
+};
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    // Satellite and mask textures span the tile including the overlap
+    let overlap = material.tile.w;
+    let tile_uv = (in.world_position.xz - material.tile.xy) / material.tile.z;
+    let uv = overlap + tile_uv * (1.0 - 2.0 * overlap);
+    let satellite = textureSample(satellite_texture, satellite_sampler, uv).rgb;
+    let mask = textureSample(mask_texture, mask_sampler, uv);
+
+    // Red, green and blue weight the second to fourth layer, the first one fills the rest
+    let enabled = select(vec4<f32>(0.0), vec4<f32>(1.0), vec4<u32>(0u, 1u, 2u, 3u) < vec4<u32>(material.layer_count));
+    var weights = vec4<f32>(max(1.0 - mask.r - mask.g - mask.b, 0.0), mask.r, mask.g, mask.b) * enabled;
+    weights = weights / max(dot(weights, vec4<f32>(1.0)), 0.0001);
+
+    // Layers repeat, independent of the sampler address mode
+    let detail_uv = in.world_position.xz * material.detail_scale;
+    let detail_uv_dx = dpdx(detail_uv);
+    let detail_uv_dy = dpdy(detail_uv);
+    let wrapped_uv = fract(detail_uv);
+    let detail = textureSampleGrad(layer0_texture, layer0_sampler, wrapped_uv, detail_uv_dx, detail_uv_dy).rgb * weights.x
+        + textureSampleGrad(layer1_texture, layer1_sampler, wrapped_uv, detail_uv_dx, detail_uv_dy).rgb * weights.y
+        + textureSampleGrad(layer2_texture, layer2_sampler, wrapped_uv, detail_uv_dx, detail_uv_dy).rgb * weights.z
+        + textureSampleGrad(layer3_texture, layer3_sampler, wrapped_uv, detail_uv_dx, detail_uv_dy).rgb * weights.w;
+
+    // Layers modulate the satellite color close to the camera, and fade out with distance
+    let distance = length(view.world_position.xyz - in.world_position.xyz);
+    let fade = clamp(distance / material.fade_distance, 0.0, 1.0);
+    let color = mix(satellite * detail * 2.0, satellite, fade);
+
+    var pbr_input: PbrInput = pbr_input_new();
+    pbr_input.material.base_color = vec4<f32>(color, 1.0);
+    pbr_input.frag_coord = in.frag_coord;
+    pbr_input.world_position = in.world_position;
+    pbr_input.world_normal = normalize(in.world_normal);
+    pbr_input.is_orthographic = view.projection[3].w == 1.0;
+    pbr_input.N = pbr_input.world_normal;
+    pbr_input.V = calculate_view(in.world_position, pbr_input.is_orthographic);
+    var output_color = pbr(pbr_input);
+#ifdef TONEMAP_IN_SHADER
+    output_color = tone_mapping(output_color);
+#endif
+    return output_color;
+}
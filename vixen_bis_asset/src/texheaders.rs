@@ -0,0 +1,226 @@
+use std::io::Read;
+
+use anyhow::{bail, Result};
+use bevy::{prelude::*, utils::HashMap};
+use byteorder::{LittleEndian, ReadBytesExt};
+use thiserror::Error;
+
+use crate::{p3d::read_asciiz, paa::PaaFormat};
+
+#[derive(Error, Debug)]
+enum TexHeadersError {
+    #[error("invalid magic")]
+    InvalidMagic,
+    #[error("unknown version: {0}")]
+    UnknownVersion(u32),
+}
+
+/// Metadata of all PAAs of binarized addons, from their texHeaders.bin, which allows answering
+/// queries without decoding textures.
+///
+/// Filled with the headers of all mounted PBOs by [`PboAssetIoPlugin`](crate::PboAssetIoPlugin).
+#[derive(Resource, Clone, Debug, Default)]
+pub struct TexHeaders {
+    /// Headers by lowercase path with forward slashes.
+    pub headers: HashMap<String, TexHeader>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TexHeader {
+    /// Path as stored, relative to the addon prefix.
+    pub path: String,
+    pub format: Option<PaaFormat>,
+    pub average_color: Color,
+    pub maximum_color: Color,
+    pub is_alpha: bool,
+    pub is_transparent: bool,
+    pub mipmaps: Vec<TexHeaderMipmap>,
+    pub file_size: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TexHeaderMipmap {
+    pub width: u16,
+    pub height: u16,
+    /// Offset of the mipmap in the PAA.
+    pub offset: u32,
+}
+
+impl TexHeader {
+    /// Returns the size of the largest mipmap.
+    pub fn size(&self) -> UVec2 {
+        self.mipmaps.first().map_or(UVec2::ZERO, |mipmap| {
+            UVec2::new(mipmap.width as u32, mipmap.height as u32)
+        })
+    }
+
+    fn read_from<R: Read>(input: &mut R) -> Result<Self> {
+        let _palette_count = input.read_u32::<LittleEndian>()?;
+        let _palette = input.read_u32::<LittleEndian>()?;
+        let mut average_color = [0.0; 4];
+        input.read_f32_into::<LittleEndian>(&mut average_color)?;
+        let _average_color = input.read_u32::<LittleEndian>()?;
+        let maximum_color = input.read_u32::<LittleEndian>()?.to_le_bytes();
+        let _clamp_flags = input.read_u32::<LittleEndian>()?;
+        let _transparent_color = input.read_u32::<LittleEndian>()?;
+        let _has_maximum_color = input.read_u8()?;
+        let is_alpha = input.read_u8()? != 0;
+        let is_transparent = input.read_u8()? != 0;
+        let _is_alpha_non_opaque = input.read_u8()?;
+        let _mipmap_count = input.read_u32::<LittleEndian>()?;
+        let format = PaaFormat::from_index(input.read_u32::<LittleEndian>()?);
+        let _little_endian = input.read_u8()?;
+        let _is_paa = input.read_u8()?;
+        let path = read_asciiz(input)?;
+        let _suffix = input.read_u32::<LittleEndian>()?;
+
+        let mipmap_count = input.read_u32::<LittleEndian>()?;
+        let mut mipmaps = Vec::with_capacity(mipmap_count.min(16) as usize);
+        for _ in 0..mipmap_count {
+            let width = input.read_u16::<LittleEndian>()?;
+            let height = input.read_u16::<LittleEndian>()?;
+            let _reserved = input.read_u16::<LittleEndian>()?;
+            let _format = input.read_u8()?;
+            let _reserved = input.read_u8()?;
+            let offset = input.read_u32::<LittleEndian>()?;
+            mipmaps.push(TexHeaderMipmap {
+                width,
+                height,
+                offset,
+            });
+        }
+        let file_size = input.read_u32::<LittleEndian>()?;
+
+        // Colors are stored as ARGB
+        let [blue, green, red, alpha] = maximum_color;
+        Ok(Self {
+            path,
+            format,
+            average_color: Color::rgba(
+                average_color[0],
+                average_color[1],
+                average_color[2],
+                average_color[3],
+            ),
+            maximum_color: Color::rgba_u8(red, green, blue, alpha),
+            is_alpha,
+            is_transparent,
+            mipmaps,
+            file_size,
+        })
+    }
+}
+
+impl TexHeaders {
+    /// Reads a texHeaders.bin, paths are prefixed with the prefix of its addon.
+    pub fn read_from<R: Read>(input: &mut R, prefix: &str) -> Result<Self> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != b"0DHT" {
+            bail!(TexHeadersError::InvalidMagic)
+        }
+        let version = input.read_u32::<LittleEndian>()?;
+        if version != 1 {
+            bail!(TexHeadersError::UnknownVersion(version))
+        }
+
+        let mut tex_headers = Self::default();
+        for _ in 0..input.read_u32::<LittleEndian>()? {
+            let header = TexHeader::read_from(input)?;
+            let path = if prefix.is_empty() {
+                header.path.clone()
+            } else {
+                format!("{}\\{}", prefix.trim_end_matches(['\\', '/']), header.path)
+            };
+            tex_headers.headers.insert(normalize_path(&path), header);
+        }
+
+        Ok(tex_headers)
+    }
+
+    /// Returns the header of a PAA, paths are case-insensitive and may use either slash.
+    pub fn get(&self, path: &str) -> Option<&TexHeader> {
+        self.headers.get(&normalize_path(path))
+    }
+
+    /// Returns the headers of the PAAs in a directory by their path, sorted by path, e.g. for
+    /// browsing textures. Paths are case-insensitive and may use either slash.
+    pub fn directory(&self, directory: &str) -> Vec<(&str, &TexHeader)> {
+        let directory = normalize_path(directory);
+        let directory = directory.trim_end_matches('/');
+        let mut headers: Vec<_> = self
+            .headers
+            .iter()
+            .filter(|(path, _)| {
+                path.rsplit_once('/')
+                    .map_or(directory.is_empty(), |(parent, _)| parent == directory)
+            })
+            .map(|(path, header)| (path.as_str(), header))
+            .collect();
+        headers.sort_by_key(|(path, _)| *path);
+        headers
+    }
+
+    /// Adds all headers of another addon, replacing existing ones.
+    pub fn extend(&mut self, other: TexHeaders) {
+        self.headers.extend(other.headers);
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    path.trim_start_matches(['\\', '/'])
+        .replace('\\', "/")
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(path: &str, width: u16, height: u16) -> TexHeader {
+        TexHeader {
+            path: path.to_string(),
+            format: Some(PaaFormat::Dxt1),
+            average_color: Color::GRAY,
+            maximum_color: Color::WHITE,
+            is_alpha: false,
+            is_transparent: false,
+            mipmaps: vec![TexHeaderMipmap {
+                width,
+                height,
+                offset: 0,
+            }],
+            file_size: 0,
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let mut tex_headers = TexHeaders::default();
+        for (path, width) in [
+            ("a3/data/b_co.paa", 256),
+            ("a3/data/a_co.paa", 512),
+            ("a3/data/nested/c_co.paa", 64),
+            ("d_co.paa", 32),
+        ] {
+            tex_headers
+                .headers
+                .insert(path.to_string(), header(path, width, width));
+        }
+
+        assert_eq!(
+            tex_headers.get("\\A3\\Data\\A_CO.paa").unwrap().size(),
+            UVec2::new(512, 512)
+        );
+        assert!(tex_headers.get("a3/data/e_co.paa").is_none());
+        assert_eq!(
+            tex_headers
+                .directory("a3\\data\\")
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect::<Vec<_>>(),
+            ["a3/data/a_co.paa", "a3/data/b_co.paa"]
+        );
+        assert_eq!(tex_headers.directory("").len(), 1);
+    }
+}
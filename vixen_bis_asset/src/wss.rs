@@ -0,0 +1,234 @@
+use std::io::{Cursor, Read};
+
+use anyhow::{bail, Result};
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    audio::AudioSource,
+};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use thiserror::Error;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+
+#[derive(Default)]
+pub struct WssLoader;
+
+impl AssetLoader for WssLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move { load_wss(bytes, load_context).await })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["wss"]
+    }
+}
+
+#[derive(Error, Debug)]
+enum WssError {
+    #[error("invalid magic")]
+    InvalidMagic,
+    #[error("unknown compression: {0}")]
+    UnknownCompression(u32),
+    #[error("unsupported format: {0}")]
+    UnsupportedFormat(u16),
+}
+
+async fn load_wss<'a, 'b>(bytes: &'a [u8], load_context: &'a mut LoadContext<'b>) -> Result<()> {
+    let file = Wss::read_from(&mut Cursor::new(bytes))?;
+
+    load_context.set_default_asset(LoadedAsset::new(AudioSource {
+        bytes: file.to_wav().into(),
+    }));
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Wss {
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+    data: Vec<u8>,
+}
+
+impl Wss {
+    fn read_from<R: Read>(input: &mut R) -> Result<Self> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != b"WSS0" {
+            bail!(WssError::InvalidMagic)
+        }
+
+        let compression = input.read_u32::<LittleEndian>()?;
+        let format = input.read_u16::<LittleEndian>()?;
+        let channels = input.read_u16::<LittleEndian>()?.max(1);
+        let sample_rate = input.read_u32::<LittleEndian>()?;
+        let _bytes_per_second = input.read_u32::<LittleEndian>()?;
+        let _block_align = input.read_u16::<LittleEndian>()?;
+        let bits_per_sample = input.read_u16::<LittleEndian>()?;
+        let _extra_size = input.read_u16::<LittleEndian>()?;
+        if format != WAVE_FORMAT_PCM {
+            bail!(WssError::UnsupportedFormat(format))
+        }
+
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+
+        Ok(match compression {
+            0 => Self {
+                channels,
+                sample_rate,
+                bits_per_sample,
+                data,
+            },
+            // Delta compressed 16-bit samples, with 8 or 4 bits per delta
+            8 | 4 => Self {
+                channels,
+                sample_rate,
+                bits_per_sample: 16,
+                data: decompress_delta(&data, channels as usize, compression == 4),
+            },
+            _ => bail!(WssError::UnknownCompression(compression)),
+        })
+    }
+
+    fn to_wav(&self) -> Vec<u8> {
+        let block_align = self.channels * (self.bits_per_sample / 8).max(1);
+
+        let mut output = Vec::with_capacity(44 + self.data.len());
+        output.extend_from_slice(b"RIFF");
+        output
+            .write_u32::<LittleEndian>(36 + self.data.len() as u32)
+            .unwrap();
+        output.extend_from_slice(b"WAVE");
+
+        output.extend_from_slice(b"fmt ");
+        output.write_u32::<LittleEndian>(16).unwrap();
+        output.write_u16::<LittleEndian>(WAVE_FORMAT_PCM).unwrap();
+        output.write_u16::<LittleEndian>(self.channels).unwrap();
+        output.write_u32::<LittleEndian>(self.sample_rate).unwrap();
+        output
+            .write_u32::<LittleEndian>(self.sample_rate * block_align as u32)
+            .unwrap();
+        output.write_u16::<LittleEndian>(block_align).unwrap();
+        output
+            .write_u16::<LittleEndian>(self.bits_per_sample)
+            .unwrap();
+
+        output.extend_from_slice(b"data");
+        output
+            .write_u32::<LittleEndian>(self.data.len() as u32)
+            .unwrap();
+        output.extend_from_slice(&self.data);
+
+        output
+    }
+}
+
+/// Decompresses signed logarithmic deltas into 16-bit samples, deltas are interleaved per
+/// channel.
+fn decompress_delta(data: &[u8], channels: usize, nibbles: bool) -> Vec<u8> {
+    let deltas: Vec<i8> = if nibbles {
+        // High nibble first, sign-extended
+        data.iter()
+            .flat_map(|&value| [(value as i8) >> 4, ((value << 4) as i8) >> 4])
+            .collect()
+    } else {
+        data.iter().map(|&value| value as i8).collect()
+    };
+
+    // Largest delta maps to the largest sample
+    let maximum = if nibbles { 7.0 } else { 127.0 };
+    let magic = (i16::MAX as f64).ln() / maximum;
+
+    let mut samples = vec![0i32; channels];
+    let mut output = Vec::with_capacity(deltas.len() * 2);
+    for (index, &delta) in deltas.iter().enumerate() {
+        let sample = &mut samples[index % channels];
+        if delta != 0 {
+            let magnitude = ((delta.unsigned_abs() as f64) * magic).exp().round() as i32;
+            *sample = (*sample + magnitude * delta.signum() as i32)
+                .clamp(i16::MIN as i32, i16::MAX as i32);
+        }
+        output.extend_from_slice(&(*sample as i16).to_le_bytes());
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_wss(compression: u32, channels: u16, data: &[u8]) -> Vec<u8> {
+        let mut output = b"WSS0".to_vec();
+        output.write_u32::<LittleEndian>(compression).unwrap();
+        output.write_u16::<LittleEndian>(WAVE_FORMAT_PCM).unwrap();
+        output.write_u16::<LittleEndian>(channels).unwrap();
+        output.write_u32::<LittleEndian>(22050).unwrap();
+        output
+            .write_u32::<LittleEndian>(22050 * 2 * channels as u32)
+            .unwrap();
+        output.write_u16::<LittleEndian>(2 * channels).unwrap();
+        output.write_u16::<LittleEndian>(16).unwrap();
+        output.write_u16::<LittleEndian>(0).unwrap();
+        output.extend_from_slice(data);
+        output
+    }
+
+    fn read_samples(compression: u32, channels: u16, data: &[u8]) -> Vec<i16> {
+        let wss = Wss::read_from(&mut write_wss(compression, channels, data).as_slice()).unwrap();
+        assert_eq!(wss.channels, channels);
+        assert_eq!(wss.bits_per_sample, 16);
+        wss.data
+            .chunks_exact(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn delta_8bit_mono() {
+        // Deltas of 16, 64 and 127 are 4, 189 and 32767, sums are clamped
+        let deltas = [64, 64, 0, -16, 127, -127, -127, -127];
+        let data = deltas.map(|delta: i8| delta as u8);
+        assert_eq!(
+            read_samples(8, 1, &data),
+            [189, 378, 378, 374, 32767, 0, -32767, -32768]
+        );
+    }
+
+    #[test]
+    fn delta_8bit_stereo() {
+        let deltas = [16, -16, 1, 64];
+        let data = deltas.map(|delta: i8| delta as u8);
+        assert_eq!(read_samples(8, 2, &data), [4, -4, 5, 185]);
+    }
+
+    #[test]
+    fn delta_4bit_mono() {
+        // Deltas of 1 and 3 are 4 and 86
+        assert_eq!(read_samples(4, 1, &[0x13, 0xD0]), [4, 90, 4, 4]);
+    }
+
+    #[test]
+    fn delta_4bit_stereo() {
+        // Deltas of 7 and -8 are 32767 and -144710
+        assert_eq!(
+            read_samples(4, 2, &[0x71, 0xF9, 0x08, 0x30]),
+            [32767, 4, 32763, -32763, 32763, -32768, 32767, -32768]
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let data = write_wss(8, 2, &[]);
+        assert!(Wss::read_from(&mut &data[..data.len() - 1]).is_err());
+
+        // Deltas of an incomplete frame are still decoded
+        assert_eq!(read_samples(8, 2, &[16, -16i8 as u8, 1]), [4, -4, 5]);
+    }
+}
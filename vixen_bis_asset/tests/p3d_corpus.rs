@@ -0,0 +1,118 @@
+use std::{fs, path::Path};
+
+use vixen_bis_asset::{check_mlod, P3dError, P3dSettings, P3dValidation};
+
+fn corpus(name: &str) -> Vec<(String, Vec<u8>)> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/corpus/p3d")
+        .join(name);
+    let mut files: Vec<_> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            (path.display().to_string(), fs::read(&path).unwrap())
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn valid() {
+    for (path, bytes) in corpus("valid") {
+        match check_mlod(&bytes, &P3dSettings::default()) {
+            Ok(diagnostics) => assert!(diagnostics.0.is_empty(), "{path}: {diagnostics:?}"),
+            Err(error) => panic!("{path}: {error}"),
+        }
+    }
+}
+
+/// Expected error of each corrupt file, recoverable ones are reported as diagnostics in lenient
+/// mode.
+fn expected_error(path: &str) -> (fn(&P3dError) -> bool, bool) {
+    let name = Path::new(path).file_stem().unwrap().to_str().unwrap();
+    match name {
+        "bad_magic" | "bad_lod_magic" => (
+            |error| matches!(error, P3dError::InvalidMagic { .. }),
+            false,
+        ),
+        "bad_version" => (
+            |error| matches!(error, P3dError::UnknownVersion { .. }),
+            true,
+        ),
+        "bad_vertex_count" => (
+            |error| matches!(error, P3dError::InvalidVertexCount { .. }),
+            true,
+        ),
+        "point_out_of_bounds" => (
+            |error| matches!(error, P3dError::PointOutOfBounds { .. }),
+            true,
+        ),
+        "normal_out_of_bounds" => (
+            |error| matches!(error, P3dError::NormalOutOfBounds { .. }),
+            true,
+        ),
+        "empty" | "huge_lod_count" | "huge_point_count" | "huge_tag" | "truncated" => (
+            |error| matches!(error, P3dError::UnexpectedEndOfFile { .. }),
+            false,
+        ),
+        _ => panic!("{path} has no expected error"),
+    }
+}
+
+#[test]
+fn corrupt() {
+    for (path, bytes) in corpus("corrupt") {
+        let (expected, _) = expected_error(&path);
+        match check_mlod(&bytes, &P3dSettings::default()) {
+            Ok(_) => panic!("{path} was accepted"),
+            Err(error) => assert!(expected(&error), "{path}: {error}"),
+        }
+    }
+}
+
+#[test]
+fn lenient() {
+    // Malformed faces and unknown versions are recoverable, structural errors are not
+    let settings = P3dSettings {
+        validation: P3dValidation::Lenient,
+        ..Default::default()
+    };
+    for (path, bytes) in corpus("corrupt") {
+        let (expected, recoverable) = expected_error(&path);
+        match check_mlod(&bytes, &settings) {
+            Ok(diagnostics) => {
+                assert!(recoverable, "{path} was accepted");
+                assert_eq!(diagnostics.0.len(), 1, "{path}: {diagnostics:?}");
+                assert!(expected(&diagnostics.0[0]), "{path}: {diagnostics:?}");
+            }
+            Err(error) => assert!(!recoverable && expected(&error), "{path}: {error}"),
+        }
+    }
+}
+
+#[test]
+fn truncated() {
+    for (path, bytes) in corpus("valid") {
+        for length in 0..bytes.len() {
+            assert!(
+                check_mlod(&bytes[..length], &P3dSettings::default()).is_err(),
+                "{path} truncated to {length} bytes was accepted"
+            );
+        }
+    }
+}
+
+#[test]
+fn bit_flips() {
+    // Flipped bits may result in valid files, but must never panic
+    for (_, bytes) in corpus("valid") {
+        for index in 0..bytes.len() {
+            for bit in 0..8 {
+                let mut bytes = bytes.clone();
+                bytes[index] ^= 1 << bit;
+                let _ = check_mlod(&bytes, &P3dSettings::default());
+            }
+        }
+    }
+}